@@ -125,12 +125,147 @@ pub async fn initialize_db() -> Result<()> {
     println!("All Tables Created Successfully");
     Ok(())
 }
+/// Schema changes made after the initial tables were created.
+/// Every statement must be idempotent as this runs against existing databases on each build.
+pub async fn update_db() -> Result<()> {
+    let pool = PgPool::connect(&get_db_url()?).await?;
+    let mut tx = pool.begin().await?;
+    let sql_commands = r#"
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS collection VARCHAR(50) DEFAULT NULL;
+
+        CREATE TABLE IF NOT EXISTS public.requirement_lists (
+        list_id SERIAL PRIMARY KEY,
+        name VARCHAR(100) NOT NULL,
+        guild_id bigint REFERENCES guild_settings(guild_id) ON DELETE CASCADE,
+        owner_id bigint,
+        description TEXT,
+        created timestamp with time zone NOT NULL DEFAULT now()
+        );
+        DROP INDEX IF EXISTS requirement_lists_name_unique;
+        CREATE UNIQUE INDEX IF NOT EXISTS requirement_lists_shared_name_unique ON public.requirement_lists USING btree (name) WHERE guild_id IS NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS requirement_lists_guild_name_unique ON public.requirement_lists USING btree (guild_id, name) WHERE guild_id IS NOT NULL;
+
+        CREATE TABLE IF NOT EXISTS public.list_subscriptions (
+        guild_id bigint NOT NULL REFERENCES guild_settings(guild_id) ON DELETE CASCADE,
+        list_id INT NOT NULL REFERENCES requirement_lists(list_id) ON DELETE CASCADE,
+        created timestamp with time zone NOT NULL DEFAULT now(),
+        PRIMARY KEY (guild_id, list_id)
+        );
+
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS list_id INT REFERENCES requirement_lists(list_id) ON DELETE CASCADE;
+
+        INSERT INTO requirement_lists (name, description) VALUES
+        ('Ascendancies', 'The Dragonsgrasp Ascendancies'),
+        ('Inn Items', 'Every item from the Inn at the End of Time')
+        ON CONFLICT DO NOTHING;
+
+        UPDATE requirements SET guild_id = NULL, list_id = (SELECT list_id FROM requirement_lists WHERE guild_id IS NULL AND name = 'Ascendancies') WHERE guild_id = 1249 AND list_id IS NULL;
+        UPDATE requirements SET guild_id = NULL, list_id = (SELECT list_id FROM requirement_lists WHERE guild_id IS NULL AND name = 'Inn Items') WHERE guild_id = 864 AND list_id IS NULL;
+        DELETE FROM guild_settings WHERE guild_id IN (1249, 864);
+
+        INSERT INTO requirement_lists (guild_id, name)
+        SELECT DISTINCT guild_id, COALESCE(collection, 'Roles') FROM requirements WHERE list_id IS NULL AND guild_id IS NOT NULL
+        ON CONFLICT DO NOTHING;
+        UPDATE requirements r SET list_id = l.list_id FROM requirement_lists l
+        WHERE r.list_id IS NULL AND l.guild_id = r.guild_id AND l.name = COALESCE(r.collection, 'Roles');
+
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS start_date DATE DEFAULT NULL;
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS end_date DATE DEFAULT NULL;
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS require_active BOOLEAN NOT NULL DEFAULT FALSE;
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS points INT DEFAULT NULL;
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS category VARCHAR(100) DEFAULT NULL;
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS ladder VARCHAR(255) DEFAULT NULL;
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS excluded_items TEXT[] NOT NULL DEFAULT '{}';
+        ALTER TABLE public.requirements ADD COLUMN IF NOT EXISTS excluded_tags TEXT[] NOT NULL DEFAULT '{}';
+
+        CREATE TABLE IF NOT EXISTS public.guild_config (
+        guild_id bigint PRIMARY KEY REFERENCES guild_settings(guild_id) ON DELETE CASCADE,
+        prefix VARCHAR(5),
+        timezone VARCHAR(64),
+        default_lookup VARCHAR(50),
+        ephemeral BOOLEAN NOT NULL DEFAULT FALSE,
+        evaluation_mode VARCHAR(20),
+        log_channel_id bigint
+        );
+
+        CREATE TABLE IF NOT EXISTS public.announcement_targets (
+        target_id SERIAL PRIMARY KEY,
+        guild_id bigint NOT NULL REFERENCES guild_settings(guild_id) ON DELETE CASCADE,
+        channel_id bigint NOT NULL,
+        role_id bigint,
+        feed VARCHAR(50),
+        keywords TEXT[] NOT NULL DEFAULT '{}',
+        created timestamp with time zone NOT NULL DEFAULT now(),
+        UNIQUE (guild_id, channel_id)
+        );
+        INSERT INTO announcement_targets (guild_id, channel_id, role_id)
+        SELECT guild_id, announcement_channel_id, announcement_role_id FROM guild_settings WHERE announcement_channel_id IS NOT NULL
+        ON CONFLICT DO NOTHING;
+
+        ALTER TABLE public.df_characters ADD COLUMN IF NOT EXISTS is_main BOOLEAN NOT NULL DEFAULT FALSE;
+        ALTER TABLE public.df_characters ADD COLUMN IF NOT EXISTS position INT DEFAULT NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS df_characters_main_unique ON public.df_characters USING btree (discord_id) WHERE is_main;
+
+        CREATE EXTENSION IF NOT EXISTS pg_trgm;
+        CREATE INDEX IF NOT EXISTS df_characters_name_trgm ON public.df_characters USING gin (character_name gin_trgm_ops);
+
+        ALTER TABLE public.df_characters ADD COLUMN IF NOT EXISTS name_checked timestamp with time zone DEFAULT NULL;
+        ALTER TABLE public.df_characters ADD COLUMN IF NOT EXISTS not_found_since timestamp with time zone DEFAULT NULL;
+
+        CREATE TABLE IF NOT EXISTS public.character_transfers (
+        transfer_id SERIAL PRIMARY KEY,
+        df_id INT NOT NULL,
+        from_discord_id bigint NOT NULL,
+        to_discord_id bigint NOT NULL,
+        transferred_by VARCHAR(50) NOT NULL,
+        guild_id bigint,
+        created timestamp with time zone NOT NULL DEFAULT now()
+        );
+
+        CREATE TABLE IF NOT EXISTS public.audit_log (
+        audit_id SERIAL PRIMARY KEY,
+        guild_id bigint,
+        actor_id bigint,
+        actor_name VARCHAR(50) NOT NULL,
+        action VARCHAR(50) NOT NULL,
+        before TEXT,
+        after TEXT,
+        created timestamp with time zone NOT NULL DEFAULT now()
+        );
+        CREATE INDEX IF NOT EXISTS audit_log_guild_created ON public.audit_log USING btree (guild_id, created DESC);
+
+        ALTER TABLE public.guild_settings ADD COLUMN IF NOT EXISTS active BOOLEAN NOT NULL DEFAULT TRUE;
+        ALTER TABLE public.guild_settings ADD COLUMN IF NOT EXISTS left_at timestamp with time zone DEFAULT NULL;
+
+        CREATE TABLE IF NOT EXISTS public.registration_requests (
+        request_id SERIAL PRIMARY KEY,
+        guild_id bigint NOT NULL REFERENCES guild_settings(guild_id) ON DELETE CASCADE,
+        discord_id bigint NOT NULL,
+        discord_name VARCHAR(50) NOT NULL,
+        df_id INT NOT NULL,
+        character_name VARCHAR(32) NOT NULL,
+        status VARCHAR(20) NOT NULL DEFAULT 'Pending',
+        reviewed_by VARCHAR(50),
+        created timestamp with time zone NOT NULL DEFAULT now(),
+        reviewed timestamp with time zone
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS registration_requests_pending_unique ON public.registration_requests USING btree (guild_id, df_id) WHERE status = 'Pending';
+"#;
+    for sql_statement in sql_commands.split(';').map(|s| s.trim()) {
+        if !sql_statement.is_empty() {
+            sqlx::query(sql_statement).execute(&mut *tx).await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
     if db_needs_to_be_created().await? {
         initialize_db().await.expect("failed initializing db");
     }
+    update_db().await.expect("failed updating db");
     // This print staementment is what tells cargo to only rerun the build script when the .env file is altered.
     // if commented out the build script will always run when built.
     println!("cargo:rerun-if-changed=.env");
@@ -0,0 +1,249 @@
+use crate::audit::{audit_change, AuditAction};
+use crate::embeds;
+use crate::requests::{AQW_DESIGN_NOTES_LINK, AQW_LINK, DESIGN_NOTES_LINK, DF_LINK};
+use crate::serenity::{ChannelType, GuildChannel, Role};
+use crate::update_checker::DesignNote;
+use crate::{Context, Error};
+use color_eyre::Result;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, PgPool};
+
+/// Where an update was posted, targets can filter on it.
+/// Every feed is a design notes site the update checker watches.
+#[derive(poise::ChoiceParameter, PartialEq, Clone, Copy, Debug)]
+pub enum UpdateFeed {
+    DragonFable,
+    AdventureQuestWorlds,
+}
+impl UpdateFeed {
+    pub const ALL: [UpdateFeed; 2] = [UpdateFeed::DragonFable, UpdateFeed::AdventureQuestWorlds];
+    /// The game's site, design note links and images are relative to it.
+    pub fn site(&self) -> &'static str {
+        let site = match self {
+            UpdateFeed::DragonFable => DF_LINK,
+            UpdateFeed::AdventureQuestWorlds => AQW_LINK,
+        };
+        site.trim_end_matches('/')
+    }
+    /// The feed's design notes for a month.
+    pub fn design_notes_url(&self, year: i32, month: u32) -> String {
+        let link = match self {
+            UpdateFeed::DragonFable => DESIGN_NOTES_LINK,
+            UpdateFeed::AdventureQuestWorlds => AQW_DESIGN_NOTES_LINK,
+        };
+        format!("{link}/{year}/{month}")
+    }
+}
+/// A channel a guild's update announcements are sent to.
+#[derive(sqlx::FromRow, Serialize, Deserialize, Getters, Debug)]
+#[getset(get = "pub")]
+pub struct AnnouncementTarget {
+    target_id: i32,
+    guild_id: i64,
+    channel_id: i64,
+    role_id: Option<i64>,
+    feed: Option<String>,
+    keywords: Vec<String>,
+}
+impl AnnouncementTarget {
+    /// The feed this target is limited to, `None` for every feed or a feed that is no longer watched.
+    pub fn feed_filter(&self) -> Option<UpdateFeed> {
+        self.feed.as_deref()?.parse().ok()
+    }
+    /// Whether an update from `feed` named `update_name` should be sent here.
+    /// Keywords match case insensitively anywhere in the name, no keywords matches everything.
+    pub fn matches(&self, feed: UpdateFeed, update_name: &str) -> bool {
+        let name = update_name.to_lowercase();
+        // a feed that is no longer watched never matches instead of matching every feed
+        (self.feed.is_none() || self.feed_filter() == Some(feed))
+            && (self.keywords.is_empty() || self.keywords.iter().any(|k| name.contains(k)))
+    }
+    pub fn matches_note(&self, note: &DesignNote) -> bool {
+        self.matches(*note.feed(), note.update_name())
+    }
+    /// The target's channel and filters in one line, for the audit log.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Announcements in <#{}>", self.channel_id);
+        if let Some(role_id) = self.role_id {
+            summary.push_str(&format!(" pinging <@&{role_id}>"));
+        }
+        if let Some(feed) = &self.feed {
+            summary.push_str(&format!(" for {feed}"));
+        }
+        if !self.keywords.is_empty() {
+            summary.push_str(&format!(" matching {}", self.keywords.join(", ")));
+        }
+        summary
+    }
+}
+/// Splits a comma separated keyword list, lowercased with blanks removed.
+pub fn parse_keywords(keywords: &str) -> Vec<String> {
+    keywords
+        .split(',')
+        .map(|k| k.trim().to_lowercase())
+        .filter(|k| !k.is_empty())
+        .collect()
+}
+/// Adds a target, or replaces the filters of the guild's target already in that channel.
+pub async fn upsert_target(
+    guild_id: i64,
+    channel_id: i64,
+    role_id: Option<i64>,
+    feed: Option<UpdateFeed>,
+    keywords: &[String],
+    pool: &PgPool,
+) -> Result<AnnouncementTarget> {
+    let target = query_as!(
+        AnnouncementTarget,
+        "INSERT INTO announcement_targets (guild_id, channel_id, role_id, feed, keywords)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (guild_id, channel_id)
+DO UPDATE SET
+    role_id = EXCLUDED.role_id,
+    feed = EXCLUDED.feed,
+    keywords = EXCLUDED.keywords
+RETURNING target_id, guild_id, channel_id, role_id, feed, keywords",
+        guild_id,
+        channel_id,
+        role_id,
+        feed.map(|f| f.to_string()),
+        keywords
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(target)
+}
+/// Removes the guild's target in a channel, returns whether there was one.
+/// Clears the `/init_announcements` channel too when it was that target.
+pub async fn remove_target(guild_id: i64, channel_id: i64, pool: &PgPool) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    let res = query!(
+        "DELETE FROM announcement_targets WHERE guild_id = $1 and channel_id = $2",
+        guild_id,
+        channel_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    query!(
+        "UPDATE guild_settings SET announcement_channel_id = NULL, announcement_role_id = NULL
+WHERE guild_id = $1 and announcement_channel_id = $2",
+        guild_id,
+        channel_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(res.rows_affected() > 0)
+}
+pub async fn get_guild_targets(guild_id: i64, pool: &PgPool) -> Result<Vec<AnnouncementTarget>> {
+    let targets = query_as!(
+        AnnouncementTarget,
+        "select target_id, guild_id, channel_id, role_id, feed, keywords
+from announcement_targets where guild_id = $1 order by target_id",
+        guild_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(targets)
+}
+/// Every active guild's targets, for sending an update.
+pub async fn get_all_targets(pool: &PgPool) -> Result<Vec<AnnouncementTarget>> {
+    let targets = query_as!(
+        AnnouncementTarget,
+        "select t.target_id, t.guild_id, t.channel_id, t.role_id, t.feed, t.keywords
+from announcement_targets t join guild_settings g on g.guild_id = t.guild_id
+where g.active order by t.guild_id, t.target_id"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(targets)
+}
+/// Manage where this server's update announcements are sent
+#[poise::command(
+    slash_command,
+    subcommands("add", "remove", "list"),
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn announcements(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+/// Send announcements to a channel, adding it again replaces its filters
+#[poise::command(slash_command, required_permissions = "ADMINISTRATOR", guild_only)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "channel announcements are sent to"] channel: GuildChannel,
+    #[description = "role pinged by announcements"] role: Option<Role>,
+    #[description = "only announce updates from this game"] feed: Option<UpdateFeed>,
+    #[description = "comma separated words, only announce updates with one in their name"]
+    keywords: Option<String>,
+) -> Result<(), Error> {
+    if !matches!(channel.kind, ChannelType::Text | ChannelType::News) {
+        return Ok(embeds::invalid_setting_embed(
+            ctx,
+            format!("<#{}> is not a text channel", channel.id.0),
+        )
+        .await?);
+    }
+    let pool = ctx.data().db();
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let guild_name = ctx.guild().unwrap().name;
+    query!(
+        "INSERT INTO guild_settings (guild_id, guild_name)
+VALUES ($1, $2)
+ON CONFLICT (guild_id)
+DO NOTHING",
+        guild_id,
+        guild_name
+    )
+    .execute(pool)
+    .await?;
+    let keywords = keywords.as_deref().map(parse_keywords).unwrap_or_default();
+    let target = upsert_target(
+        guild_id,
+        channel.id.0 as i64,
+        role.map(|r| r.id.0 as i64),
+        feed,
+        &keywords,
+        pool,
+    )
+    .await?;
+    audit_change(ctx, AuditAction::AnnouncementChange, None, Some(target.summary())).await?;
+    let targets = get_guild_targets(guild_id, pool).await?;
+    embeds::announcement_targets_embed(ctx, &targets).await?;
+    Ok(())
+}
+/// Stop sending announcements to a channel
+#[poise::command(slash_command, required_permissions = "ADMINISTRATOR", guild_only)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "channel to stop announcing in"] channel: GuildChannel,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    if !remove_target(guild_id, channel.id.0 as i64, pool).await? {
+        ctx.reply(format!("<#{}> is not an announcement channel", channel.id.0))
+            .await?;
+        return Ok(());
+    }
+    audit_change(
+        ctx,
+        AuditAction::AnnouncementChange,
+        Some(format!("Announcements in <#{}>", channel.id.0)),
+        None,
+    )
+    .await?;
+    let targets = get_guild_targets(guild_id, pool).await?;
+    embeds::announcement_targets_embed(ctx, &targets).await?;
+    Ok(())
+}
+/// Show where this server's announcements are sent
+#[poise::command(slash_command, required_permissions = "ADMINISTRATOR", guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let targets = get_guild_targets(guild_id, ctx.data().db()).await?;
+    embeds::announcement_targets_embed(ctx, &targets).await?;
+    Ok(())
+}
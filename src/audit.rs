@@ -0,0 +1,217 @@
+use crate::dev_tools::is_superuser_check;
+use crate::embeds;
+use crate::paginate::{paginate, PaginateEmbed};
+use crate::serenity::{Color, User};
+use crate::{Context, Error};
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use getset::Getters;
+use sqlx::{query, query_as, PgPool};
+use std::fmt::Write;
+/// Most entries `/audit` looks through.
+const MAX_AUDIT_ENTRIES: i64 = 500;
+const ENTRIES_PER_PAGE: usize = 8;
+/// Longest before or after summary shown, so a page fits in an embed.
+const MAX_SUMMARY_LENGTH: usize = 200;
+
+/// What kind of change an audit entry records.
+#[derive(poise::ChoiceParameter, PartialEq, Clone, Copy, Debug)]
+pub enum AuditAction {
+    #[name = "Character Register"]
+    CharacterRegister,
+    #[name = "Character Delete"]
+    CharacterDelete,
+    #[name = "Character Transfer"]
+    CharacterTransfer,
+    #[name = "Character Import"]
+    CharacterImport,
+    #[name = "Registration Review"]
+    RegistrationReview,
+    #[name = "Requirement Upload"]
+    RequirementUpload,
+    #[name = "List Delete"]
+    ListDelete,
+    #[name = "List Subscription"]
+    ListSubscription,
+    #[name = "Announcement Change"]
+    AnnouncementChange,
+    #[name = "Settings Change"]
+    SettingsChange,
+    #[name = "Backup Restore"]
+    BackupRestore,
+    #[name = "Update Checker"]
+    UpdateChecker,
+    #[name = "Guild Leave"]
+    GuildLeave,
+}
+/// A recorded change, kept after the guild's other data is purged.
+#[derive(sqlx::FromRow, Getters, Debug)]
+#[getset(get = "pub")]
+pub struct AuditEntry {
+    audit_id: i32,
+    guild_id: Option<i64>,
+    actor_id: Option<i64>,
+    actor_name: String,
+    action: String,
+    before: Option<String>,
+    after: Option<String>,
+    created: DateTime<Utc>,
+}
+impl AuditEntry {
+    fn page_line(&self) -> String {
+        let actor = match self.actor_id {
+            Some(id) => format!("<@{id}>"),
+            None => self.actor_name.clone(),
+        };
+        let mut line = format!(
+            "<t:{}:f> **{}** by {actor}",
+            self.created.timestamp(),
+            self.action
+        );
+        let shorten = |summary: &Option<String>| {
+            summary
+                .as_ref()
+                .map(|s| match s.char_indices().nth(MAX_SUMMARY_LENGTH) {
+                    Some((end, _)) => format!("{}…", &s[..end]),
+                    None => s.clone(),
+                })
+        };
+        match (shorten(&self.before), shorten(&self.after)) {
+            (Some(before), Some(after)) => {
+                let _ = write!(line, "\n~~{before}~~ → {after}");
+            }
+            (Some(before), None) => {
+                let _ = write!(line, "\n~~{before}~~");
+            }
+            (None, Some(after)) => {
+                let _ = write!(line, "\n{after}");
+            }
+            (None, None) => {}
+        }
+        line
+    }
+}
+/// Records a change, `actor_id` is `None` for changes Discord made, like being removed from a guild.
+pub async fn record_audit(
+    pool: &PgPool,
+    guild_id: Option<i64>,
+    actor_id: Option<i64>,
+    actor_name: &str,
+    action: AuditAction,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<()> {
+    query!(
+        "INSERT INTO audit_log (guild_id, actor_id, actor_name, action, before, after) VALUES ($1, $2, $3, $4, $5, $6)",
+        guild_id,
+        actor_id,
+        actor_name,
+        action.to_string(),
+        before,
+        after
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+/// Records a change made by the command's author in the command's guild.
+pub async fn audit_change(
+    ctx: Context<'_>,
+    action: AuditAction,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().map(|id| id.0 as i64);
+    audit_author_change(ctx, guild_id, action, before, after).await
+}
+/// Records a bot-wide change made by the command's author, like a shared list upload,
+/// shown by `/audit global` rather than in the guild it was run in.
+pub async fn audit_global_change(
+    ctx: Context<'_>,
+    action: AuditAction,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<()> {
+    audit_author_change(ctx, None, action, before, after).await
+}
+async fn audit_author_change(
+    ctx: Context<'_>,
+    guild_id: Option<i64>,
+    action: AuditAction,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<()> {
+    let author = ctx.author();
+    record_audit(
+        ctx.data().db(),
+        guild_id,
+        Some(author.id.0 as i64),
+        &author.name,
+        action,
+        before,
+        after,
+    )
+    .await
+}
+/// A guild's newest entries first, or the bot-wide ones for `None`,
+/// optionally only one actor's or one kind of change.
+pub async fn get_audit_log(
+    pool: &PgPool,
+    guild_id: Option<i64>,
+    actor_id: Option<i64>,
+    action: Option<AuditAction>,
+) -> Result<Vec<AuditEntry>> {
+    let entries = query_as!(
+        AuditEntry,
+        "SELECT audit_id, guild_id, actor_id, actor_name, action, before, after, created FROM audit_log
+WHERE (guild_id = $1 OR ($1::BIGINT IS NULL AND guild_id IS NULL)) and ($2::BIGINT IS NULL OR actor_id = $2) and ($3::TEXT IS NULL OR action = $3)
+ORDER BY created DESC, audit_id DESC LIMIT $4",
+        guild_id,
+        actor_id,
+        action.map(|a| a.to_string()),
+        MAX_AUDIT_ENTRIES
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(entries)
+}
+/// Show who changed what in this server
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn audit(
+    ctx: Context<'_>,
+    #[description = "only changes made by this user"] actor: Option<User>,
+    #[description = "only this kind of change"] action: Option<AuditAction>,
+    #[description = "Show bot-wide changes instead of this server's (super users only)"]
+    global: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = if global.unwrap_or(false) {
+        if !is_superuser_check(ctx).await? {
+            embeds::superuser_only_embed(ctx).await?;
+            return Ok(());
+        }
+        None
+    } else {
+        Some(ctx.guild_id().unwrap().0 as i64)
+    };
+    let actor_id = actor.map(|a| a.id.0 as i64);
+    let entries = get_audit_log(ctx.data().db(), guild_id, actor_id, action).await?;
+    let pages: Vec<String> = entries
+        .chunks(ENTRIES_PER_PAGE)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(AuditEntry::page_line)
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        })
+        .collect();
+    let embed = PaginateEmbed::new("Audit Log", None, Color::DARK_GOLD, pages)
+        .set_empty_string("No changes recorded");
+    paginate(ctx, embed).await?;
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use crate::announcements::{get_guild_targets, AnnouncementTarget};
+use crate::audit::{audit_change, AuditAction};
+use crate::embeds;
+use crate::guild_config::{get_guild_config, send_guild_log_or_warn, upsert_guild_config, GuildConfig};
+use crate::guild_settings::insert_requirement_list;
+use crate::requirement_lists::upsert_list;
+use crate::requirements::{get_collection, guild_member_ids, RequirementList};
+use crate::serenity::Attachment;
+use crate::{Context, Error};
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool};
+/// Version written to new archives, restoring refuses anything newer.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Everything stored about a guild, written by `/backup` and read by `/restore`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GuildArchive {
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    pub guild_id: i64,
+    pub guild_name: String,
+    pub announcement_channel_id: Option<i64>,
+    pub announcement_role_id: Option<i64>,
+    pub config: GuildConfig,
+    #[serde(default)]
+    pub announcement_targets: Vec<AnnouncementTarget>,
+    /// The guild's own lists, e.g. its roles and Inn items.
+    #[serde(default)]
+    pub lists: Vec<ArchivedList>,
+    /// Names of the shared lists the guild subscribes to.
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+    /// Registered users who were members of the guild.
+    #[serde(default)]
+    pub users: Vec<ArchivedUser>,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchivedList {
+    pub name: String,
+    pub description: Option<String>,
+    pub requirements: RequirementList,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchivedUser {
+    pub discord_id: i64,
+    pub discord_name: String,
+    pub registered_by: String,
+    pub characters: Vec<ArchivedCharacter>,
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchivedCharacter {
+    pub df_id: i32,
+    pub character_name: String,
+    pub registered_by: String,
+    #[serde(default)]
+    pub is_main: bool,
+    #[serde(default)]
+    pub position: Option<i32>,
+}
+/// What a restore wrote, anything already registered to someone else is skipped.
+#[derive(Default, Debug)]
+pub struct RestoreSummary {
+    pub lists: usize,
+    pub requirements: usize,
+    pub subscriptions: u64,
+    pub missing_subscriptions: Vec<String>,
+    pub users: u64,
+    pub characters: u64,
+    pub skipped_characters: u64,
+}
+/// Archives a guild and the registrations of `member_ids`, `None` when the guild was never stored.
+pub async fn export_guild(
+    guild_id: i64,
+    member_ids: &[i64],
+    pool: &PgPool,
+) -> Result<Option<GuildArchive>> {
+    let settings = match query!(
+        "select guild_name, announcement_channel_id, announcement_role_id from guild_settings where guild_id = $1",
+        guild_id
+    )
+    .fetch_optional(pool)
+    .await?
+    {
+        Some(settings) => settings,
+        None => return Ok(None),
+    };
+    let mut lists = Vec::new();
+    for list in query!(
+        "select name, description from requirement_lists where guild_id = $1 order by name",
+        guild_id
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        lists.push(ArchivedList {
+            requirements: get_collection(guild_id, &list.name, pool).await?,
+            name: list.name,
+            description: list.description,
+        });
+    }
+    let subscriptions = query!(
+        "select l.name from list_subscriptions s join requirement_lists l on l.list_id = s.list_id
+where s.guild_id = $1 order by l.name",
+        guild_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.name)
+    .collect();
+    let mut users: Vec<ArchivedUser> = query!(
+        "select discord_id, discord_name, registered_by from users where discord_id = ANY($1) order by discord_id",
+        member_ids
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|u| ArchivedUser {
+        discord_id: u.discord_id,
+        discord_name: u.discord_name,
+        registered_by: u.registered_by,
+        characters: Vec::new(),
+    })
+    .collect();
+    for char in query!(
+        "select discord_id, df_id, character_name, registered_by, is_main, position from df_characters
+where discord_id = ANY($1) order by is_main desc, position asc nulls last, created",
+        member_ids
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        if let Some(user) = users.iter_mut().find(|u| u.discord_id == char.discord_id) {
+            user.characters.push(ArchivedCharacter {
+                df_id: char.df_id,
+                character_name: char.character_name,
+                registered_by: char.registered_by,
+                is_main: char.is_main,
+                position: char.position,
+            });
+        }
+    }
+    Ok(Some(GuildArchive {
+        version: ARCHIVE_VERSION,
+        created: Utc::now(),
+        guild_id,
+        guild_name: settings.guild_name,
+        announcement_channel_id: settings.announcement_channel_id,
+        announcement_role_id: settings.announcement_role_id,
+        config: get_guild_config(guild_id, pool).await?,
+        announcement_targets: get_guild_targets(guild_id, pool).await?,
+        lists,
+        subscriptions,
+        users,
+    }))
+}
+/// Replaces a guild's data with an archive's in one transaction.
+/// Channels and roles only exist in the guild they came from,
+/// so announcements and the log channel are only restored into the archived guild.
+pub async fn restore_guild(
+    archive: GuildArchive,
+    guild_id: i64,
+    guild_name: &str,
+    pool: &PgPool,
+) -> Result<RestoreSummary> {
+    if archive.version > ARCHIVE_VERSION {
+        return Err(eyre!(
+            "Archive version {} is newer than the supported version {ARCHIVE_VERSION}",
+            archive.version
+        ));
+    }
+    let same_guild = archive.guild_id == guild_id;
+    let mut summary = RestoreSummary::default();
+    let mut transaction = pool.begin().await?;
+    query!(
+        "INSERT INTO guild_settings (guild_id, guild_name) VALUES ($1, $2)
+ON CONFLICT (guild_id) DO UPDATE SET guild_name = EXCLUDED.guild_name",
+        guild_id,
+        guild_name
+    )
+    .execute(&mut *transaction)
+    .await?;
+    upsert_guild_config(&archive.config.for_guild(guild_id), &mut transaction).await?;
+    if same_guild {
+        query!(
+            "UPDATE guild_settings SET announcement_channel_id = $2, announcement_role_id = $3 WHERE guild_id = $1",
+            guild_id,
+            archive.announcement_channel_id,
+            archive.announcement_role_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        query!("DELETE FROM announcement_targets WHERE guild_id = $1", guild_id)
+            .execute(&mut *transaction)
+            .await?;
+        for target in &archive.announcement_targets {
+            query!(
+                "INSERT INTO announcement_targets (guild_id, channel_id, role_id, feed, keywords) VALUES ($1, $2, $3, $4, $5)",
+                guild_id,
+                target.channel_id(),
+                *target.role_id(),
+                target.feed().as_deref(),
+                target.keywords()
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+    let list_names: Vec<String> = archive.lists.iter().map(|l| l.name.clone()).collect();
+    query!(
+        "DELETE FROM requirement_lists WHERE guild_id = $1 and name <> ALL($2)",
+        guild_id,
+        &list_names
+    )
+    .execute(&mut *transaction)
+    .await?;
+    for list in &archive.lists {
+        let list_id = upsert_list(
+            &mut transaction,
+            Some(guild_id),
+            &list.name,
+            None,
+            list.description.as_deref(),
+        )
+        .await?;
+        insert_requirement_list(list_id, &mut transaction, &list.requirements).await?;
+        summary.lists += 1;
+        summary.requirements += list.requirements.requirements().len();
+    }
+    query!("DELETE FROM list_subscriptions WHERE guild_id = $1", guild_id)
+        .execute(&mut *transaction)
+        .await?;
+    summary.subscriptions = query!(
+        "INSERT INTO list_subscriptions (guild_id, list_id)
+SELECT $1, list_id FROM requirement_lists WHERE guild_id IS NULL and name = ANY($2)",
+        guild_id,
+        &archive.subscriptions
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    if summary.subscriptions < archive.subscriptions.len() as u64 {
+        let existing = query!(
+            "select name from requirement_lists where guild_id is null and name = ANY($1)",
+            &archive.subscriptions
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        summary.missing_subscriptions = archive
+            .subscriptions
+            .iter()
+            .filter(|name| !existing.iter().any(|r| &r.name == *name))
+            .cloned()
+            .collect();
+    }
+    for user in &archive.users {
+        summary.users += query!(
+            "INSERT INTO users (discord_id, discord_name, registered_by) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            user.discord_id,
+            user.discord_name,
+            user.registered_by
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        for char in &user.characters {
+            // the user may not exist if their name was taken by someone else since the backup
+            let inserted = query!(
+                "INSERT INTO df_characters (discord_id, df_id, character_name, registered_by, is_main, position)
+SELECT $1, $2, $3, $4, $5 AND NOT EXISTS (select 1 from df_characters where discord_id = $1 and is_main), $6
+WHERE EXISTS (select 1 from users where discord_id = $1)
+ON CONFLICT DO NOTHING",
+                user.discord_id,
+                char.df_id,
+                char.character_name,
+                char.registered_by,
+                char.is_main,
+                char.position
+            )
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+            summary.characters += inserted;
+            summary.skipped_characters += 1 - inserted;
+        }
+    }
+    transaction.commit().await?;
+    Ok(summary)
+}
+/// Download everything the bot stores about this server
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn backup(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let member_ids = guild_member_ids(ctx, guild_id).await?;
+    let archive = match export_guild(guild_id, &member_ids, ctx.data().db()).await? {
+        Some(archive) => archive,
+        None => {
+            ctx.say("This guild has nothing stored, use `/init_guild` to register it.")
+                .await?;
+            return Ok(());
+        }
+    };
+    let bytes = serde_json::to_vec_pretty(&archive)?;
+    embeds::backup_embed(ctx, &archive, bytes).await?;
+    Ok(())
+}
+/// Replace this server's stored data with a backup, which may be from another server
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn restore(
+    ctx: Context<'_>,
+    #[description = "JSON backup from /backup"] file: Attachment,
+) -> Result<(), Error> {
+    if let Some(file_type) = &file.content_type {
+        let mime = file_type.split(';').next().unwrap_or_default().trim();
+        if mime != "application/json" {
+            embeds::wrong_file_type(ctx, file_type).await?;
+            return Ok(());
+        }
+    }
+    ctx.defer_ephemeral().await?;
+    let archive: GuildArchive = match serde_json::from_slice(&file.download().await?) {
+        Ok(archive) => archive,
+        Err(why) => return Ok(embeds::invalid_backup_embed(ctx, why.to_string()).await?),
+    };
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let guild_name = ctx.guild().unwrap().name;
+    let pool = ctx.data().db();
+    let from = format!("{} ({})", archive.guild_name, archive.created.format("%Y-%m-%d"));
+    let summary = match restore_guild(archive, guild_id, &guild_name, pool).await {
+        Ok(summary) => summary,
+        Err(why) => return Ok(embeds::invalid_backup_embed(ctx, why.to_string()).await?),
+    };
+    ctx.data().requirement_cache().invalidate_guild(guild_id).await;
+    ctx.data().guild_config().invalidate(guild_id);
+    audit_change(ctx, AuditAction::BackupRestore, None, Some(format!("Restored {from}"))).await?;
+    embeds::restore_summary_embed(ctx, &from, &summary).await?;
+    let config = ctx.data().guild_config().get(guild_id, pool).await?;
+    send_guild_log_or_warn(
+        ctx,
+        &config,
+        "Backup Restored",
+        format!("Restored {from}\n*by <@{}>*", ctx.author().id.0),
+    )
+    .await;
+    Ok(())
+}
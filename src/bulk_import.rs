@@ -0,0 +1,327 @@
+use crate::audit::{audit_change, AuditAction};
+use crate::embeds;
+use crate::guild_config::send_guild_log_or_warn;
+use crate::manage_users::insert_registration_in;
+use crate::parsing::fetch_character_names;
+use crate::serenity::{Attachment, UserId};
+use crate::{Context, Error};
+use color_eyre::{eyre::eyre, Result};
+use serde_json::Value;
+use sqlx::{query, PgExecutor, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+/// Rows past this are reported as invalid instead of fetched.
+pub const MAX_IMPORT_ROWS: usize = 500;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ImportFormat {
+    Csv,
+    Json,
+}
+impl ImportFormat {
+    /// Picks the format from an attachment's content type, falling back to its extension.
+    pub fn from_attachment(file: &Attachment) -> Option<Self> {
+        let mime = file
+            .content_type
+            .as_deref()
+            .and_then(|t| t.split(';').next())
+            .unwrap_or_default()
+            .trim();
+        let filename = file.filename.to_lowercase();
+        match mime {
+            "application/json" => Some(Self::Json),
+            "text/csv" => Some(Self::Csv),
+            _ if filename.ends_with(".json") => Some(Self::Json),
+            _ if filename.ends_with(".csv") || filename.ends_with(".txt") => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+#[derive(poise::ChoiceParameter, PartialEq, Clone, Copy, Debug)]
+pub enum ImportStatus {
+    Registered,
+    #[name = "Already Registered"]
+    AlreadyRegistered,
+    #[name = "Not Found"]
+    NotFound,
+    Invalid,
+}
+/// A row of an import file that parsed.
+#[derive(PartialEq, Debug)]
+pub struct ImportRow {
+    pub line: usize,
+    pub discord_id: i64,
+    pub df_id: i32,
+}
+/// What happened to a row, ids are kept as written so invalid rows can be reported.
+#[derive(Debug)]
+pub struct ImportResult {
+    pub line: usize,
+    pub discord_id: String,
+    pub df_id: String,
+    pub status: ImportStatus,
+    pub detail: String,
+}
+impl ImportResult {
+    fn new(row: &ImportRow, status: ImportStatus, detail: impl Into<String>) -> Self {
+        Self {
+            line: row.line,
+            discord_id: row.discord_id.to_string(),
+            df_id: row.df_id.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+    fn invalid(line: usize, discord_id: String, df_id: String, detail: impl Into<String>) -> Self {
+        Self {
+            line,
+            discord_id,
+            df_id,
+            status: ImportStatus::Invalid,
+            detail: detail.into(),
+        }
+    }
+}
+/// Splits a file into rows of discord id and DF ID, and results for the rows that can't be imported.
+/// CSV rows are `discord_id,df_id` with an optional header, JSON is an array of
+/// `{"discord_id": ..., "df_id": ...}` objects where ids may be numbers or strings.
+pub fn parse_import(
+    text: &str,
+    format: ImportFormat,
+) -> Result<(Vec<ImportRow>, Vec<ImportResult>)> {
+    let fields: Vec<(usize, String, String)> = match format {
+        ImportFormat::Csv => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let mut cols = line
+                    .split(',')
+                    .map(|c| c.trim().trim_matches('"').to_owned());
+                (
+                    i + 1,
+                    cols.next().unwrap_or_default(),
+                    cols.next().unwrap_or_default(),
+                )
+            })
+            .collect(),
+        ImportFormat::Json => {
+            let entries: Vec<Value> = serde_json::from_str(text)?;
+            let field = |entry: &Value, key: &str| match &entry[key] {
+                Value::String(s) => s.trim().to_owned(),
+                Value::Number(n) => n.to_string(),
+                _ => String::new(),
+            };
+            entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| (i + 1, field(entry, "discord_id"), field(entry, "df_id")))
+                .collect()
+        }
+    };
+    let mut rows = Vec::new();
+    let mut invalid = Vec::new();
+    let mut seen = HashSet::new();
+    for (line, discord_id, df_id) in fields {
+        let (Ok(parsed_discord_id), Ok(parsed_df_id)) =
+            (discord_id.parse::<i64>(), df_id.parse::<i32>())
+        else {
+            // a CSV header names its columns
+            if !(format == ImportFormat::Csv && line == 1) {
+                invalid.push(ImportResult::invalid(
+                    line,
+                    discord_id,
+                    df_id,
+                    "ids must be numbers",
+                ));
+            }
+            continue;
+        };
+        if rows.len() == MAX_IMPORT_ROWS {
+            invalid.push(ImportResult::invalid(
+                line,
+                discord_id,
+                df_id,
+                format!("over the {MAX_IMPORT_ROWS} row limit"),
+            ));
+        } else if !seen.insert(parsed_df_id) {
+            invalid.push(ImportResult::invalid(
+                line,
+                discord_id,
+                df_id,
+                "DF ID appears earlier in the file",
+            ));
+        } else {
+            rows.push(ImportRow {
+                line,
+                discord_id: parsed_discord_id,
+                df_id: parsed_df_id,
+            });
+        }
+    }
+    if rows.is_empty() && invalid.is_empty() {
+        return Err(eyre!("The file has no rows"));
+    }
+    Ok((rows, invalid))
+}
+/// Who each already registered DF ID belongs to.
+pub async fn registered_owners(
+    df_ids: &[i32],
+    executor: impl PgExecutor<'_>,
+) -> Result<HashMap<i32, i64>> {
+    let owners = query!(
+        "SELECT df_id, discord_id FROM df_characters WHERE df_id = ANY($1)",
+        df_ids
+    )
+    .fetch_all(executor)
+    .await?
+    .into_iter()
+    .map(|r| (r.df_id, r.discord_id))
+    .collect();
+    Ok(owners)
+}
+/// Registers every row in one transaction.
+/// `character_names` comes from [`fetch_character_names`], rows missing from it failed to fetch.
+pub async fn register_rows(
+    rows: &[ImportRow],
+    discord_names: &HashMap<i64, String>,
+    character_names: &HashMap<i32, Option<String>>,
+    registered_by: &str,
+    pool: &PgPool,
+) -> Result<Vec<ImportResult>> {
+    let mut transaction = pool.begin().await?;
+    let df_ids: Vec<i32> = rows.iter().map(|r| r.df_id).collect();
+    let owners = registered_owners(&df_ids, &mut *transaction).await?;
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let result = if let Some(owner) = owners.get(&row.df_id) {
+            ImportResult::new(
+                row,
+                ImportStatus::AlreadyRegistered,
+                format!("registered to {owner}"),
+            )
+        } else if let Some(discord_name) = discord_names.get(&row.discord_id) {
+            match character_names.get(&row.df_id) {
+                Some(Some(name)) => {
+                    let registered = insert_registration_in(
+                        &mut transaction,
+                        row.discord_id,
+                        discord_name,
+                        row.df_id,
+                        name,
+                        registered_by,
+                    )
+                    .await?;
+                    let status = if registered {
+                        ImportStatus::Registered
+                    } else {
+                        ImportStatus::AlreadyRegistered
+                    };
+                    ImportResult::new(row, status, name.as_str())
+                }
+                Some(None) => {
+                    ImportResult::new(row, ImportStatus::NotFound, "no character with this DF ID")
+                }
+                None => ImportResult::new(
+                    row,
+                    ImportStatus::NotFound,
+                    "character page could not be fetched",
+                ),
+            }
+        } else {
+            ImportResult::new(row, ImportStatus::Invalid, "unknown Discord user")
+        };
+        results.push(result);
+    }
+    transaction.commit().await?;
+    Ok(results)
+}
+/// The report attached to `/import_registrations`, one CSV row per input row.
+pub fn import_report(results: &[ImportResult]) -> String {
+    let mut sorted: Vec<&ImportResult> = results.iter().collect();
+    sorted.sort_by_key(|r| r.line);
+    let mut report = String::from("line,discord_id,df_id,status,detail\n");
+    for r in sorted {
+        let _ = writeln!(
+            report,
+            "{},\"{}\",\"{}\",{},\"{}\"",
+            r.line,
+            r.discord_id.replace('"', "\"\""),
+            r.df_id.replace('"', "\"\""),
+            r.status,
+            r.detail.replace('"', "\"\"")
+        );
+    }
+    report
+}
+/// Register many characters from a CSV or JSON file of Discord user IDs and DF IDs
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn import_registrations(
+    ctx: Context<'_>,
+    #[description = "CSV or JSON file of discord_id and df_id rows"] file: Attachment,
+) -> Result<(), Error> {
+    let format = match ImportFormat::from_attachment(&file) {
+        Some(format) => format,
+        None => {
+            let file_type = file.content_type.as_deref().unwrap_or(&file.filename);
+            ctx.say(format!("`{file_type}` is not a CSV or JSON file"))
+                .await?;
+            return Ok(());
+        }
+    };
+    ctx.defer_ephemeral().await?;
+    let text = String::from_utf8_lossy(&file.download().await?).into_owned();
+    let (rows, mut results) = match parse_import(&text, format) {
+        Ok(parsed) => parsed,
+        Err(why) => return Ok(embeds::invalid_import_embed(ctx, why.to_string()).await?),
+    };
+    let pool = ctx.data().db();
+    let guild = ctx.guild().unwrap();
+    let mut discord_names = HashMap::new();
+    for id in rows.iter().map(|r| r.discord_id).collect::<HashSet<i64>>() {
+        let name = match guild.members.get(&UserId(id as u64)) {
+            Some(member) => Some(member.user.name.clone()),
+            None => ctx.http().get_user(id as u64).await.ok().map(|u| u.name),
+        };
+        if let Some(name) = name {
+            discord_names.insert(id, name);
+        }
+    }
+    // characters that are already registered don't need their page fetched
+    let df_ids: Vec<i32> = rows.iter().map(|r| r.df_id).collect();
+    let owners = registered_owners(&df_ids, pool).await?;
+    let to_fetch: Vec<i32> = df_ids
+        .into_iter()
+        .filter(|id| !owners.contains_key(id))
+        .collect();
+    let character_names = fetch_character_names(&to_fetch).await;
+    let author = &ctx.author().name;
+    results.extend(register_rows(&rows, &discord_names, &character_names, author, pool).await?);
+    let report = import_report(&results);
+    embeds::import_report_embed(ctx, &results, report.into_bytes()).await?;
+    let registered = results
+        .iter()
+        .filter(|r| r.status == ImportStatus::Registered)
+        .count();
+    let summary = format!(
+        "Registered {registered} of {} characters from `{}`",
+        results.len(),
+        file.filename
+    );
+    audit_change(ctx, AuditAction::CharacterImport, None, Some(summary.clone())).await?;
+    let guild_id = guild.id.0 as i64;
+    let config = ctx.data().guild_config().get(guild_id, pool).await?;
+    send_guild_log_or_warn(
+        ctx,
+        &config,
+        "Registrations Imported",
+        format!("{summary}\n*by <@{}>*", ctx.author().id.0),
+    )
+    .await;
+    Ok(())
+}
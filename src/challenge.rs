@@ -1,7 +1,3 @@
-use crate::manage_users::autocomplete_character;
-use crate::{Context, Error};
-use color_eyre::Result;
-use poise::serenity_prelude::User;
 use serde::Serialize;
 
 #[derive(poise::ChoiceParameter, PartialEq, Debug,Serialize)]
@@ -11,6 +7,11 @@ pub enum ChallengeCategory {
     Item,
 }
 /*
+use crate::manage_users::autocomplete_character;
+use crate::{Context, Error};
+use color_eyre::Result;
+use poise::serenity_prelude::User;
+
 pub async fn autocomplete_category(
     ctx: Context<'_>,
     partial: &str,
@@ -3,13 +3,16 @@ use color_eyre::Result;
 use log::info;
 use sqlx::{query, PgPool};
 use std::env;
-pub const ASCEND_GUILD_ID:i64 = 1249;
-pub const INN_GUILD_ID:i64 = 864;
+/// Days a guild's data is kept after the bot is removed from it, unless `GUILD_RETENTION_DAYS` is set.
+pub const DEFAULT_GUILD_RETENTION_DAYS: i32 = 30;
 /// query! macros check the query against the database at COMPILE TIME
 /// So without a valid database the program will not run
+///
+/// Resolves a user's default character: their main, otherwise the first in their order.
 pub async fn query_with_id(pool: &PgPool, id: u64) -> Result<Option<i32>> {
     let result = query!(
-        "SELECT df_id FROM df_characters WHERE discord_id = $1 ORDER BY created ASC LIMIT 1",
+        "SELECT df_id FROM df_characters WHERE discord_id = $1
+ORDER BY is_main DESC, position ASC NULLS LAST, created ASC LIMIT 1",
         id as i64
     )
     .fetch_optional(pool)
@@ -30,6 +33,7 @@ pub async fn establish_connection() -> Result<PgPool> {
     info!("Connected to: {connect_string}");
     Ok(pool)
 }
+/// Stores a guild the bot is in, reactivating it if the bot had been removed from it.
 pub async fn insert_guild(pool: &PgPool, guild: &Guild) -> Result<()> {
     query!(
         "
@@ -37,7 +41,9 @@ INSERT INTO public.guild_settings (guild_id, guild_name)
 VALUES ($1, $2)
 ON CONFLICT (guild_id) 
 DO UPDATE SET
-    guild_name = EXCLUDED.guild_name",
+    guild_name = EXCLUDED.guild_name,
+    active = TRUE,
+    left_at = NULL",
         guild.id.0 as i64,
         guild.name,
     )
@@ -45,3 +51,40 @@ DO UPDATE SET
     .await?;
     Ok(())
 }
+/// Marks a guild the bot was removed from, its data is kept until [`purge_inactive_guilds`].
+/// Returns `false` when the guild was already inactive or its settings were deleted.
+pub async fn mark_guild_inactive(pool: &PgPool, guild_id: i64) -> Result<bool> {
+    let res = query!(
+        "UPDATE guild_settings SET active = FALSE, left_at = now() WHERE guild_id = $1 and active",
+        guild_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() != 0)
+}
+/// Marks every stored guild not in `guild_ids` inactive, for guilds that removed the bot while it was offline.
+pub async fn mark_missing_guilds_inactive(pool: &PgPool, guild_ids: &[i64]) -> Result<u64> {
+    let res = query!(
+        "UPDATE guild_settings SET active = FALSE, left_at = now() WHERE active and guild_id <> ALL($1)",
+        guild_ids
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+/// Deletes guilds inactive for longer than `retention_days` along with everything stored for them.
+pub async fn purge_inactive_guilds(pool: &PgPool, retention_days: i32) -> Result<u64> {
+    let res = query!(
+        "DELETE FROM guild_settings WHERE NOT active and left_at < now() - make_interval(days => $1)",
+        retention_days
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+pub fn guild_retention_days() -> i32 {
+    env::var("GUILD_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_GUILD_RETENTION_DAYS)
+}
@@ -1,24 +1,38 @@
-use crate::db::ASCEND_GUILD_ID;
+use crate::announcements::{AnnouncementTarget, UpdateFeed};
+use crate::backup::{GuildArchive, RestoreSummary};
+use crate::bulk_import::{ImportResult, ImportStatus};
+use crate::guild_config::GuildConfig;
 use crate::guild_settings::GuildSettings;
-use crate::paginate::{paginate, PaginateEmbed};
+use crate::manage_users::RegisteredCharacter;
+use crate::paginate::{get_category_pages, paginate, paginate_item, PaginateEmbed};
 use crate::parsing::{get_discord_embed_description_flash, DFCharacterData, WarList};
 use crate::requests::{
-    fetch_page_with_user_agent, ASCEND_DA_IMGUR, CHARPAGE, DA_IMGUR, DF_LINK, NDA_IMGUR,
+    fetch_page_with_user_agent, ASCEND_DA_IMGUR, CHARPAGE, DA_IMGUR, NDA_IMGUR,
     ROLE_DA_IMGUR, USER_AGENT,
 };
-use crate::requirements::{check_requirements, RequirementList, RequirementListType};
+use crate::requirement_lists::ASCENDANCIES_LIST;
+use crate::requirements::{
+    evaluate_requirements_with_mode, excluded_requirements, ladder_progress, load_inn_list,
+    EvaluationMode, Requirement, RequirementList, RequirementListType, RequirementWindow,
+};
 use crate::rng::random_rgb;
-use crate::sheets::SheetData;
+use crate::sheets::{SheetData, StatRow};
 use crate::update_checker::{DesignNote, UpdateCheckerFeatureFlag};
 use crate::{
-    serenity::{ChannelId, Color},
+    serenity::{ChannelId, Color, User},
     Context,
 };
+use chrono::Utc;
 use color_eyre::{Report, Result};
+use num_format::{Locale, ToFormattedString};
 use poise::serenity_prelude::AttachmentType;
 use scraper::{Html, Selector};
 use std::sync::Arc;
-use std::{collections::HashMap, env, fmt::Write};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    fmt::Write,
+};
 pub async fn guild_only(ctx: Context<'_>) -> Result<bool> {
     if ctx.guild().is_none() {
         guild_only_embed(ctx).await?;
@@ -47,11 +61,11 @@ pub async fn guild_only_with_id(ctx: Context<'_>) -> Result<Option<i64>> {
     }
 }
 pub async fn send_update_embed(
-    guilds: Arc<Vec<GuildSettings>>,
+    targets: Arc<Vec<AnnouncementTarget>>,
     dn: DesignNote,
     flag: UpdateCheckerFeatureFlag,
 ) -> Result<()> {
-    let url = format!("{DF_LINK}{}", dn.link());
+    let url = format!("{}{}", dn.feed().site(), dn.link());
     let token = env::var("BOT_TOKEN").unwrap();
     let http = crate::serenity::Http::new(&token);
     let document = fetch_page_with_user_agent(USER_AGENT, &url).await?;
@@ -71,20 +85,22 @@ pub async fn send_update_embed(
         text
     };
     tokio::spawn(async move {
-        for guild in guilds.iter() {
-            let channel = ChannelId(guild.announcement_channel_id().unwrap() as u64);
-            let ping = match flag {
-                UpdateCheckerFeatureFlag::ForceNoPing => "".to_owned(),
-                _ => format!(" <@&{}>", guild.announcement_role_id().unwrap()),
+        for target in targets.iter().filter(|t| t.matches_note(&dn)) {
+            let channel = ChannelId(*target.channel_id() as u64);
+            let ping = match (flag, target.role_id()) {
+                (UpdateCheckerFeatureFlag::ForceNoPing, _) | (_, None) => "".to_owned(),
+                (_, Some(role_id)) => format!(" <@&{role_id}>"),
             };
             if let Err(why) = channel
                 .send_message(&http, |f| {
                     f.content(format!("Release is  **LIVE!**{}", ping))
                         .embed(|e| {
+                            if *dn.feed() == UpdateFeed::DragonFable {
+                                e.thumbnail(DA_IMGUR);
+                            }
                             e.title(dn.update_name())
                                 .url(url.clone())
                                 .color(Color::from_rgb(254, 216, 55))
-                                .thumbnail(DA_IMGUR)
                                 .image(dn.image())
                                 .description(description.clone())
                                 .author(|a| a.name(dn.poster_name()).icon_url(dn.poster_image()))
@@ -105,28 +121,24 @@ pub async fn roles_embed(
     title: String,
 ) -> Result<()> {
     roles.sort_alphabetical();
-    let description = roles
-        .requirements()
-        .iter()
-        .fold(String::new(), |mut acc, r| {
-            writeln!(
-                acc,
-                "**{}**\n{}",
-                r.name(),
-                r.description.as_ref().unwrap_or(&"".to_string())
-            )
-            .expect("failed to parse");
-            acc
-        });
-    ctx.send(|f| {
-        f.embed(|f| {
-            f.title(title)
-                .color(Color::from_rgb(1, 214, 103))
-                .thumbnail(ROLE_DA_IMGUR)
-                .description(description)
-        })
-    })
-    .await?;
+    let today = Utc::now().date_naive();
+    let (pages, categories) = get_category_pages(roles.requirements(), |r| {
+        let window = match r.window(today) {
+            RequirementWindow::Upcoming(start) => format!(" *upcoming {start}*"),
+            RequirementWindow::Expired(end) => format!(" *expired {end}*"),
+            RequirementWindow::Open => String::new(),
+        };
+        format!(
+            "**{}**{}\n{}\n",
+            r.name(),
+            window,
+            r.description.as_deref().unwrap_or_default()
+        )
+    });
+    let embed = PaginateEmbed::new(&title, Some(ROLE_DA_IMGUR), Color::from_rgb(1, 214, 103), pages)
+        .set_categories(categories)
+        .set_empty_string("No Roles to display");
+    paginate(ctx, embed).await?;
     Ok(())
 }
 pub async fn send_roles_embed(
@@ -135,10 +147,6 @@ pub async fn send_roles_embed(
     ctx: Context<'_>,
     role_list_type: RequirementListType,
 ) -> Result<()> {
-    let guild_id = match role_list_type {
-        RequirementListType::Ascend => ASCEND_GUILD_ID,
-        RequirementListType::Roles => ctx.guild_id().unwrap().0 as i64,
-    };
     let pool = &ctx.data().db_connection;
     let name = char.name().to_owned();
     let (thumbnail, color, title) = match role_list_type {
@@ -153,21 +161,57 @@ pub async fn send_roles_embed(
             format!("{}'s Acendancies", name),
         ),
     };
-    let roles = check_requirements(&char, guild_id, pool).await?;
-    let mut description = String::new();
-    for role in roles.requirements() {
-        description += format!("__**{}**__\n{}\n", role.name(), role.description()).as_str()
+    let cache = ctx.data().requirement_cache();
+    let (list, guild_id) = match role_list_type {
+        RequirementListType::Ascend => (cache.shared_list(ASCENDANCIES_LIST, pool).await?, None),
+        RequirementListType::Roles => {
+            let guild_id = ctx.guild_id().unwrap().0 as i64;
+            (cache.requirements(guild_id, pool).await?, Some(guild_id))
+        }
+    };
+    let inn_list = load_inn_list(&list, guild_id, pool, cache).await?;
+    let mode = match guild_id {
+        Some(guild_id) => ctx.data().guild_config().get(guild_id, pool).await?.evaluation_mode(),
+        None => EvaluationMode::Highest,
+    };
+    let roles = evaluate_requirements_with_mode(&list, &char, inn_list.as_deref(), mode);
+    let (mut pages, mut categories) = get_category_pages(roles.requirements(), |role| {
+        format!("__**{}**__\n{}\n", role.name(), role.description())
+    });
+    let excluded = excluded_requirements(&list, &char, inn_list.as_deref());
+    if !excluded.is_empty() {
+        categories.push(("Excluded".to_string(), pages.len()));
+        pages.push(String::new());
+        let (mut current_len, mut current_page) = (0, pages.len() - 1);
+        for (role, violations) in excluded {
+            let line = format!("__**{}**__\nowns {}\n", role.name(), violations.join(", "));
+            paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+        }
     }
-    ctx.send(|f| {
-        f.embed(|f| {
-            f.title(title)
-                .url(format!("{CHARPAGE}{df_id}"))
-                .color(color)
-                .thumbnail(thumbnail)
-                .description(description)
-        })
-    })
-    .await?;
+    let ladders = ladder_progress(&list, &char, inn_list.as_deref());
+    if !ladders.is_empty() {
+        categories.push(("Ladder Progress".to_string(), pages.len()));
+        pages.push(String::new());
+        let (mut current_len, mut current_page) = (0, pages.len() - 1);
+        for progress in ladders {
+            let earned = progress.earned.map_or("*none yet*", |r| r.name());
+            let mut line = format!("__**{}**__\n{}\n", progress.ladder, earned);
+            match (progress.next, progress.current) {
+                (Some(next), Some(current)) => {
+                    let _ = writeln!(line, "{}/{} towards *{}*", current, next.amount(), next.name());
+                }
+                (Some(next), None) => {
+                    let _ = writeln!(line, "next: *{}*", next.name());
+                }
+                (None, _) => line.push_str("*complete*\n"),
+            }
+            paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+        }
+    }
+    let embed = PaginateEmbed::new(&title, Some(thumbnail), color, pages)
+        .set_url(format!("{CHARPAGE}{df_id}"))
+        .set_categories(categories);
+    paginate(ctx, embed).await?;
     Ok(())
 }
 pub async fn wrong_file_type(ctx: Context<'_>, file_type: &str) -> Result<()> {
@@ -175,7 +219,7 @@ pub async fn wrong_file_type(ctx: Context<'_>, file_type: &str) -> Result<()> {
         f.embed(|f| {
             f.title(format!("Wrong File Type! {file_type}"))
                 .color(Color::DARK_RED)
-                .description("The File Type must be [application/json; charset=utf-8]")
+                .description("The File Type must be [application/json]")
         })
     })
     .await?;
@@ -192,6 +236,17 @@ pub async fn guild_only_embed(ctx: Context<'_>) -> Result<()> {
     .await?;
     Ok(())
 }
+pub async fn superuser_only_embed(ctx: Context<'_>) -> Result<()> {
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title("Super User only feature!")
+                .color(Color::DARK_RED)
+                .description("The feature you are trying to use is only usable by super users!")
+        })
+    })
+    .await?;
+    Ok(())
+}
 pub async fn no_settings_embed(ctx: Context<'_>) -> Result<()> {
     ctx.send(|f| {
         f.embed(|f| {
@@ -214,6 +269,362 @@ pub async fn no_roles_embed(ctx: Context<'_>) -> Result<()> {
     .await?;
     Ok(())
 }
+pub async fn no_list_embed(ctx: Context<'_>, name: &str) -> Result<()> {
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title(format!("There is no shared list named {name}!"))
+                .color(Color::DARK_RED)
+                .description("Use /shared_lists to see the lists available to this guild.")
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// Ephemeral confirmation of a guild's stored settings.
+pub async fn guild_settings_embed(ctx: Context<'_>, settings: &GuildSettings) -> Result<()> {
+    let channel = settings
+        .announcement_channel_id()
+        .map_or("*not set*".to_string(), |id| format!("<#{id}>"));
+    let role = settings
+        .announcement_role_id()
+        .map_or("*not set*".to_string(), |id| format!("<@&{id}>"));
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title(format!("Settings for {}", settings.guild_name()))
+                .color(Color::DARK_GOLD)
+                .field("Guild ID", settings.guild_id(), false)
+                .field("Announcement Channel", channel, true)
+                .field("Announcement Role", role, true)
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// Ephemeral view of a guild's `/settings`.
+pub async fn guild_config_embed(ctx: Context<'_>, config: &GuildConfig) -> Result<()> {
+    let guild_name = ctx.guild().map(|g| g.name).unwrap_or_default();
+    let prefix = config
+        .prefix()
+        .clone()
+        .unwrap_or_else(|| format!("{} *(default)*", ctx.data().prefix()));
+    let timezone = config
+        .timezone()
+        .map_or("Server Time *(default)*".to_string(), |tz| tz.to_string());
+    let log_channel = config
+        .log_channel_id()
+        .map_or("*not set*".to_string(), |id| format!("<#{id}>"));
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title(format!("Settings for {guild_name}"))
+                .color(Color::DARK_GOLD)
+                .field("Prefix", prefix, true)
+                .field("Timezone", timezone, true)
+                .field("Default Lookup", config.default_lookup(), true)
+                .field("Ephemeral Replies", config.ephemeral(), true)
+                .field("Evaluation Mode", config.evaluation_mode(), true)
+                .field("Log Channel", log_channel, true)
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// Ephemeral list of a guild's announcement targets and their filters.
+pub async fn announcement_targets_embed(
+    ctx: Context<'_>,
+    targets: &[AnnouncementTarget],
+) -> Result<()> {
+    let guild_name = ctx.guild().map(|g| g.name).unwrap_or_default();
+    let description = targets.iter().fold(String::new(), |mut acc, t| {
+        let _ = writeln!(acc, "<#{}>", t.channel_id());
+        if let Some(role_id) = t.role_id() {
+            let _ = writeln!(acc, "**Ping:** <@&{role_id}>");
+        }
+        let feed = t.feed().as_deref().unwrap_or("All");
+        let _ = writeln!(acc, "**Feed:** {feed}");
+        if !t.keywords().is_empty() {
+            let _ = writeln!(acc, "**Keywords:** {}", t.keywords().join(", "));
+        }
+        acc.push('\n');
+        acc
+    });
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title(format!("{guild_name}'s Announcements"))
+                .color(Color::DARK_GOLD)
+                .description(if targets.is_empty() {
+                    "No announcement channels, add one with `/announcements add`".to_string()
+                } else {
+                    description
+                })
+        })
+    })
+    .await?;
+    Ok(())
+}
+pub async fn backup_embed(ctx: Context<'_>, archive: &GuildArchive, bytes: Vec<u8>) -> Result<()> {
+    let characters: usize = archive.users.iter().map(|u| u.characters.len()).sum();
+    let requirements: usize = archive
+        .lists
+        .iter()
+        .map(|l| l.requirements.requirements().len())
+        .sum();
+    let attachment = AttachmentType::Bytes {
+        data: std::borrow::Cow::Owned(bytes),
+        filename: format!("{}_backup_{}.json", archive.guild_id, archive.created.format("%Y%m%d")),
+    };
+    ctx.send(|f| {
+        f.ephemeral(true)
+            .embed(|f| {
+                f.title(format!("{} Backup", archive.guild_name))
+                    .color(Color::DARK_GOLD)
+                    .description(format!(
+                        "**Lists:** {} *({requirements} requirements)*\n**Subscriptions:** {}\n**Announcement Channels:** {}\n**Users:** {}\n**Characters:** {characters}",
+                        archive.lists.len(),
+                        archive.subscriptions.len(),
+                        archive.announcement_targets.len(),
+                        archive.users.len(),
+                    ))
+                    .footer(|f| f.text(format!("Archive version {}", archive.version)))
+            })
+            .attachment(attachment)
+    })
+    .await?;
+    Ok(())
+}
+pub async fn restore_summary_embed(
+    ctx: Context<'_>,
+    from: &str,
+    summary: &RestoreSummary,
+) -> Result<()> {
+    let mut description = format!(
+        "**Lists:** {} *({} requirements)*\n**Subscriptions:** {}\n**New Users:** {}\n**New Characters:** {}",
+        summary.lists,
+        summary.requirements,
+        summary.subscriptions,
+        summary.users,
+        summary.characters
+    );
+    if summary.skipped_characters > 0 {
+        let _ = write!(
+            description,
+            "\n*{} characters were already registered and skipped*",
+            summary.skipped_characters
+        );
+    }
+    if !summary.missing_subscriptions.is_empty() {
+        let _ = write!(
+            description,
+            "\n*Shared lists that no longer exist:* {}",
+            summary.missing_subscriptions.join(", ")
+        );
+    }
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title(format!("Restored {from}"))
+                .color(Color::DARK_GOLD)
+                .description(description)
+        })
+    })
+    .await?;
+    Ok(())
+}
+pub async fn invalid_backup_embed(ctx: Context<'_>, why: String) -> Result<()> {
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title("Invalid Backup")
+                .color(Color::DARK_RED)
+                .description(why)
+        })
+    })
+    .await?;
+    Ok(())
+}
+pub async fn invalid_import_embed(ctx: Context<'_>, why: String) -> Result<()> {
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title("Invalid Import File")
+                .color(Color::DARK_RED)
+                .description(why)
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// Counts of each outcome of a registration import, with the full report attached.
+pub async fn import_report_embed(
+    ctx: Context<'_>,
+    results: &[ImportResult],
+    report: Vec<u8>,
+) -> Result<()> {
+    let description = [
+        ImportStatus::Registered,
+        ImportStatus::AlreadyRegistered,
+        ImportStatus::NotFound,
+        ImportStatus::Invalid,
+    ]
+    .iter()
+    .map(|status| {
+        let count = results.iter().filter(|r| r.status == *status).count();
+        format!("**{status}:** {count}")
+    })
+    .collect::<Vec<String>>()
+    .join("\n");
+    let attachment = AttachmentType::Bytes {
+        data: std::borrow::Cow::Owned(report),
+        filename: format!("import_report_{}.csv", Utc::now().format("%Y%m%d")),
+    };
+    ctx.send(|f| {
+        f.ephemeral(true)
+            .embed(|f| {
+                f.title("Registration Import")
+                    .color(Color::DARK_GOLD)
+                    .description(description)
+            })
+            .attachment(attachment)
+    })
+    .await?;
+    Ok(())
+}
+pub async fn invalid_setting_embed(ctx: Context<'_>, why: String) -> Result<()> {
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title("Invalid Setting")
+                .color(Color::DARK_RED)
+                .description(why)
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// A member's characters in their order after changing it.
+pub async fn character_order_embed(
+    ctx: Context<'_>,
+    title: String,
+    chars: &[RegisteredCharacter],
+) -> Result<()> {
+    let author = ctx.author();
+    let description = chars
+        .iter()
+        .map(RegisteredCharacter::embed_line)
+        .collect::<Vec<String>>()
+        .join("\n");
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title(title)
+                .color(Color::DARK_GOLD)
+                .author(|a| a.name(&author.name).icon_url(author.face()))
+                .description(description)
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// Every character registered to a user with their stats, then the roles they earned in the guild.
+/// `chars` are the characters that could be fetched, `roles` is `None` outside a guild.
+pub async fn send_profile_embed(
+    ctx: Context<'_>,
+    user: &User,
+    registered: &[RegisteredCharacter],
+    chars: &[DFCharacterData],
+    roles: Option<RequirementList>,
+) -> Result<()> {
+    let mut pages = vec![String::new()];
+    let (mut current_len, mut current_page) = (0, 0);
+    let total_waves: i32 = chars.iter().map(|c| c.wars.calc_waves_cleared()).sum();
+    let total_gold: i64 = chars.iter().map(|c| c.gold as i64).sum();
+    let header = format!(
+        "**Characters:** {}\n**Total Gold:** {}\n**Total Waves:** {}\n\n",
+        registered.len(),
+        total_gold.to_formatted_string(&Locale::en),
+        total_waves.to_formatted_string(&Locale::en)
+    );
+    paginate_item(&mut pages, header, &mut current_len, &mut current_page);
+    for registered_char in registered {
+        let mut line = registered_char.embed_line();
+        match chars.iter().find(|c| c.id == *registered_char.df_id()) {
+            Some(char) => {
+                let _ = writeln!(
+                    line,
+                    "\n**Level:** {} **Gold:** {} **DA:** {}\n**Unique Items:** {} **Waves:** {}",
+                    char.level,
+                    char.gold.to_formatted_string(&Locale::en),
+                    if char.dragon_amulet { "✅" } else { "❌" },
+                    char.unique_item_count,
+                    char.wars.calc_waves_cleared().to_formatted_string(&Locale::en)
+                );
+            }
+            None => line.push_str("\n*could not be fetched*\n"),
+        }
+        line.push('\n');
+        paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+    }
+    let mut categories = vec![("Characters".to_string(), 0)];
+    if let Some(roles) = roles {
+        categories.push(("Roles".to_string(), pages.len()));
+        pages.push(String::new());
+        let (mut current_len, mut current_page) = (0, pages.len() - 1);
+        let header = format!("__**Earned Roles ({})**__\n", roles.requirements().len());
+        paginate_item(&mut pages, header, &mut current_len, &mut current_page);
+        if roles.requirements().is_empty() {
+            let line = "*none yet*\n".to_string();
+            paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+        }
+        for role in roles.requirements() {
+            let line = format!("{}\n", role.name());
+            paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+        }
+    }
+    let title = format!("{}'s Profile", user.name);
+    let face = user.face();
+    let embed = PaginateEmbed::new(&title, Some(&face), Color::DARK_GOLD, pages)
+        .set_categories(categories);
+    paginate(ctx, embed).await?;
+    Ok(())
+}
+/// Ephemeral confirmation of an uploaded requirement list.
+pub async fn requirements_stored_embed(
+    ctx: Context<'_>,
+    title: String,
+    requirements: &RequirementList,
+) -> Result<()> {
+    let count_by = |key: fn(&Requirement) -> String| {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for req in requirements.requirements() {
+            *counts.entry(key(req)).or_default() += 1;
+        }
+        counts.iter().fold(String::new(), |mut acc, (key, count)| {
+            let _ = writeln!(acc, "{key}: **{count}**");
+            acc
+        })
+    };
+    let reqs = requirements.requirements();
+    let ladders: HashSet<&String> = reqs.iter().filter_map(|r| r.ladder.as_ref()).collect();
+    let seasonal = reqs.iter().filter(|r| r.start.is_some() || r.end.is_some()).count();
+    let exclusions = reqs
+        .iter()
+        .filter(|r| r.excluded.is_some() || r.excluded_tags.is_some())
+        .count();
+    ctx.send(|f| {
+        f.ephemeral(true).embed(|f| {
+            f.title(title)
+                .color(Color::DARK_GOLD)
+                .thumbnail(ROLE_DA_IMGUR)
+                .description(format!("Stored **{}** requirements", reqs.len()))
+                .field("Categories", count_by(|r| r.category().to_string()), true)
+                .field("Types", count_by(|r| r.req_type.to_string()), true)
+                .field(
+                    "Other",
+                    format!(
+                        "Ladders: **{}**\nSeasonal: **{seasonal}**\nExclusions: **{exclusions}**",
+                        ladders.len()
+                    ),
+                    true,
+                )
+        })
+    })
+    .await?;
+    Ok(())
+}
 pub async fn role_init_error(ctx: Context<'_>, role_error: Report) -> Result<()> {
     ctx.send(|f| {
         f.embed(|f| {
@@ -259,23 +670,26 @@ pub async fn not_found_embed(ctx: Context<'_>, df_id: i32) -> Result<()> {
     Ok(())
 }
 pub async fn compare_not_found_embed(ctx: Context<'_>, notfound: Vec<i32>) -> Result<()> {
-    let (amount, description) = match notfound.len() {
-        2 => (("Both"), "These characters were"),
-        _ => (("One"), "This character was"),
+    let (title, description) = match notfound.len() {
+        1 => (
+            "One of the characters you searched does not exist".to_string(),
+            "This character was",
+        ),
+        n => (
+            format!("{n} of the characters you searched do not exist"),
+            "These characters were",
+        ),
     };
     let chars_description = notfound.iter().fold(String::new(), |mut acc, f| {
-        let _ = writeln!(acc, "[{f}]({CHARPAGE})");
+        let _ = writeln!(acc, "[{f}]({CHARPAGE}{f})");
         acc
     });
     ctx.send(|f| {
         f.embed(|f| {
-            f.title(format!(
-                "{} of the characters you searched does not exsit",
-                amount
-            ))
-            .color(Color::DARK_RED)
-            .description(format!("{description} not found:\n{chars_description}"))
-            .image("https://account.dragonfable.com/images/bgs/bg-df-main.jpg")
+            f.title(title)
+                .color(Color::DARK_RED)
+                .description(format!("{description} not found:\n{chars_description}"))
+                .image("https://account.dragonfable.com/images/bgs/bg-df-main.jpg")
         })
     })
     .await?;
@@ -451,28 +865,65 @@ pub async fn send_duplicates_embed(
     Ok(())
 }
 pub async fn send_compare_embed(sheet: SheetData, ctx: Context<'_>) -> Result<()> {
-    let title = format!("{} vs {}", sheet.user_one_name, sheet.user_two_name);
+    let comparison = &sheet.comparison;
+    let mut title = comparison.names().join(" vs ");
+    // embed titles are limited to 256 characters
+    if title.len() > 256 {
+        title = format!("Comparing {} characters", comparison.characters.len());
+    }
     let sheet_attachment = AttachmentType::Bytes {
         data: std::borrow::Cow::Borrowed(&sheet.buf),
         filename: format!("{}.xlsx", title.clone()),
     };
-    let description1 = format!(
-        "**{}** has *{}* unique items **{}** does not",
-        sheet.user_one_name, sheet.user_one_unique_dif, sheet.user_two_name
-    );
-    let description2 = format!(
-        "**{}** has *{}* unique items **{}** does not",
-        sheet.user_two_name, sheet.user_two_unique_dif, sheet.user_one_name
+    let mut description = format!(
+        "*{}* items are shared by all {} characters\n\n",
+        comparison.shared.len(),
+        comparison.characters.len()
     );
-
+    for char in &comparison.characters {
+        let _ = writeln!(
+            description,
+            "**[{}]({CHARPAGE}{})** has *{}* unique items and lacks *{}* items all the others have",
+            char.name,
+            char.df_id,
+            char.unique.len(),
+            char.missing.len()
+        );
+    }
+    let color = Color::from(random_rgb());
     ctx.send(|f| {
-        f.embed(|f| {
-            f.title(title)
-                .color(random_rgb())
-                .description(format!("{description1}\n{description2}"))
-        })
-        .attachment(sheet_attachment)
+        f.embed(|f| f.title(&title).color(color).description(description))
+            .attachment(sheet_attachment)
     })
     .await?;
+    let names = comparison.names();
+    let mut pages = Vec::new();
+    let mut categories = Vec::new();
+    for (category, rows) in [("Stats", &sheet.stats.stats), ("Wars", &sheet.stats.wars)] {
+        if rows.is_empty() {
+            continue;
+        }
+        categories.push((category.to_string(), pages.len()));
+        pages.push(String::new());
+        let (mut current_len, mut current_page) = (0, pages.len() - 1);
+        for row in rows {
+            let line = stat_row_lines(row, &names);
+            paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+        }
+    }
+    let embed = PaginateEmbed::new(&title, None, color, pages).set_categories(categories);
+    paginate(ctx, embed).await?;
     Ok(())
 }
+/// A stat's value for each character, the winners' values in bold with a trophy.
+fn stat_row_lines(row: &StatRow, names: &[&str]) -> String {
+    let mut lines = format!("__**{}**__\n", row.label);
+    for (i, (name, value)) in names.iter().zip(&row.values).enumerate() {
+        if row.winners.contains(&i) {
+            let _ = writeln!(lines, "{name}: **{value}** 🏆");
+        } else {
+            let _ = writeln!(lines, "{name}: {value}");
+        }
+    }
+    lines
+}
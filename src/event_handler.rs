@@ -1,33 +1,114 @@
-use crate::db::insert_guild;
+use crate::audit::{record_audit, AuditAction};
+use crate::db::{
+    guild_retention_days, insert_guild, mark_guild_inactive, mark_missing_guilds_inactive,
+    purge_inactive_guilds,
+};
+use crate::name_refresh::spawn_name_refresh;
+use crate::onboarding::start_onboarding;
+use crate::registration::{handle_registration_button, REGISTRATION_BUTTON};
 use crate::{Data, Error};
 use color_eyre::Result;
-use log::info;
+use log::{error, info};
+use tokio::time::{self, Duration};
 use poise::serenity_prelude as serenity;
 use poise::Event;
+const GUILD_PURGE: &str = "guild_purge";
+const NAME_REFRESH: &str = "name_refresh";
+/// How often guilds past their retention period are purged.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
 #[allow(unused)]
 pub async fn event_handler(
-    _ctx: &serenity::Context,
+    ctx: &serenity::Context,
     event: &Event<'_>,
     framework: poise::FrameworkContext<'_, Data, Error>,
     data: &Data,
 ) -> Result<(), Error> {
     match event {
         Event::Ready { data_about_bot } => {
-            let prefix =                 framework
-                    .options()
-                    .prefix_options
-                    .prefix
-                    .as_deref().expect("No Prefix");
             info!(
                 "Logged in as: {} with prefix: {}",
                 data_about_bot.user.name,
-                prefix
+                data.prefix()
             );
+            let pool = data.db();
+            let guild_ids: Vec<i64> = data_about_bot.guilds.iter().map(|g| g.id.0 as i64).collect();
+            match mark_missing_guilds_inactive(pool, &guild_ids).await {
+                Ok(0) => {}
+                Ok(count) => info!("Marked {count} guilds that removed the bot while offline inactive"),
+                Err(why) => error!("Failed marking missing guilds inactive: {why}"),
+            }
+            // Ready fires again on reconnects, only one purge loop should run
+            if !data.tasks().is_running(GUILD_PURGE).await {
+                data.tasks().start_task(GUILD_PURGE).await;
+                let pool = pool.clone();
+                let retention_days = guild_retention_days();
+                tokio::spawn(async move {
+                    let mut interval = time::interval(PURGE_INTERVAL);
+                    loop {
+                        interval.tick().await;
+                        match purge_inactive_guilds(&pool, retention_days).await {
+                            Ok(0) => {}
+                            Ok(count) => info!("Purged {count} guilds inactive for over {retention_days} days"),
+                            Err(why) => error!("Failed purging inactive guilds: {why}"),
+                        }
+                    }
+                });
+            }
+            if !data.tasks().is_running(NAME_REFRESH).await {
+                data.tasks().start_task(NAME_REFRESH).await;
+                spawn_name_refresh(ctx.clone(), pool.clone());
+            }
         }
         Event::GuildCreate { guild, is_new } => {
             info!("Initilazing guild {}:{}", guild.id.0, guild.name);
             let pool = &data.db_connection;
             insert_guild(pool, guild).await;
+            if *is_new {
+                // a rejoined guild may have been purged while its old data was still cached
+                data.requirement_cache().invalidate_guild(guild.id.0 as i64).await;
+                data.guild_config().invalidate(guild.id.0 as i64);
+                // each step waits on admins, so the wizard can't hold up other events
+                let (ctx, guild, data) = (ctx.clone(), guild.clone(), data.clone());
+                tokio::spawn(async move {
+                    if let Err(why) = start_onboarding(&ctx, &guild, &data).await {
+                        error!("Failed onboarding guild {}: {why}", guild.id.0);
+                    }
+                });
+            }
+        }
+        // unavailable means a discord outage, not that the bot was removed
+        Event::GuildDelete { incomplete, .. } if !incomplete.unavailable => {
+            info!("Removed from guild {}", incomplete.id.0);
+            let marked = match mark_guild_inactive(data.db(), incomplete.id.0 as i64).await {
+                Ok(marked) => marked,
+                Err(why) => {
+                    error!("Failed marking guild {} inactive: {why}", incomplete.id.0);
+                    // still record the removal
+                    true
+                }
+            };
+            // `leave_guild` already deleted the guild's settings and audited the leave
+            if marked {
+                let audit = record_audit(
+                    data.db(),
+                    Some(incomplete.id.0 as i64),
+                    None,
+                    "Discord",
+                    AuditAction::GuildLeave,
+                    None,
+                    Some("Removed from the guild".to_owned()),
+                );
+                if let Err(why) = audit.await {
+                    error!("Failed auditing removal from guild {}: {why}", incomplete.id.0);
+                }
+            }
+        }
+        Event::InteractionCreate {
+            interaction: serenity::Interaction::MessageComponent(press),
+        } if press.data.custom_id.starts_with(REGISTRATION_BUTTON) => {
+            if let Err(why) = handle_registration_button(ctx, press, data).await {
+                error!("Failed reviewing registration {}: {why}", press.data.custom_id);
+            }
         }
         _ => {}
     }
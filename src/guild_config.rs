@@ -0,0 +1,459 @@
+use crate::audit::{audit_change, AuditAction};
+use crate::embeds;
+use crate::lookup_df::LookupCategory;
+use crate::requirements::EvaluationMode;
+use crate::serenity::{ChannelId, ChannelType, Color, GuildChannel, Http};
+use crate::{Context, Data, Error};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use color_eyre::Result;
+use getset::Getters;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, PgConnection, PgPool};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+/// Longest prefix a guild can set.
+pub const MAX_PREFIX_LEN: usize = 5;
+
+/// Per guild options set with `/settings`, anything unset falls back to the bot's defaults.
+#[derive(sqlx::FromRow, Serialize, Deserialize, Getters, Default, Clone, Debug)]
+pub struct GuildConfig {
+    #[getset(get = "pub")]
+    guild_id: i64,
+    #[getset(get = "pub")]
+    prefix: Option<String>,
+    timezone: Option<String>,
+    default_lookup: Option<String>,
+    #[getset(get = "pub")]
+    ephemeral: bool,
+    evaluation_mode: Option<String>,
+    #[getset(get = "pub")]
+    log_channel_id: Option<i64>,
+}
+impl GuildConfig {
+    pub fn new(guild_id: i64) -> GuildConfig {
+        GuildConfig {
+            guild_id,
+            ..Default::default()
+        }
+    }
+    /// The same options for another guild, without the log channel as it belongs to this guild.
+    pub fn for_guild(self, guild_id: i64) -> GuildConfig {
+        if guild_id == self.guild_id {
+            return self;
+        }
+        GuildConfig {
+            guild_id,
+            log_channel_id: None,
+            ..self
+        }
+    }
+    /// Each option's stored value, for showing what a change replaced.
+    fn option_values(&self) -> [(&'static str, String); 6] {
+        let value = |v: Option<String>| v.unwrap_or_else(|| "default".to_owned());
+        [
+            ("Prefix", value(self.prefix.clone())),
+            ("Timezone", value(self.timezone.clone())),
+            ("Default Lookup", value(self.default_lookup.clone())),
+            ("Ephemeral Replies", self.ephemeral.to_string()),
+            ("Evaluation Mode", value(self.evaluation_mode.clone())),
+            ("Log Channel", value(self.log_channel_id.map(|id| format!("<#{id}>")))),
+        ]
+    }
+    /// The options that differ from `old`, as before and after summaries.
+    pub fn changes_from(&self, old: &GuildConfig) -> (String, String) {
+        let (before, after): (Vec<String>, Vec<String>) = old
+            .option_values()
+            .into_iter()
+            .zip(self.option_values())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((name, old), (_, new))| (format!("{name}: {old}"), format!("{name}: {new}")))
+            .unzip();
+        (before.join(", "), after.join(", "))
+    }
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone.as_deref()?.parse().ok()
+    }
+    /// Category `/lookup_df_character` uses when none is given.
+    pub fn default_lookup(&self) -> LookupCategory {
+        self.default_lookup
+            .as_deref()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(LookupCategory::CharacterPage)
+    }
+    pub fn evaluation_mode(&self) -> EvaluationMode {
+        self.evaluation_mode
+            .as_deref()
+            .and_then(|m| m.parse().ok())
+            .unwrap_or_default()
+    }
+    pub fn set_prefix(&mut self, prefix: Option<String>) -> Result<(), String> {
+        if let Some(prefix) = &prefix {
+            if prefix.is_empty() || prefix.chars().count() > MAX_PREFIX_LEN {
+                return Err(format!("A prefix must be 1 to {MAX_PREFIX_LEN} characters long"));
+            }
+            if prefix.chars().any(char::is_whitespace) {
+                return Err("A prefix can't contain spaces".to_string());
+            }
+        }
+        self.prefix = prefix;
+        Ok(())
+    }
+    pub fn set_timezone(&mut self, timezone: Option<&str>) -> Result<(), String> {
+        self.timezone = match timezone {
+            Some(timezone) => match timezone.parse::<Tz>() {
+                Ok(tz) => Some(tz.name().to_string()),
+                Err(_) => return Err(format!("**{timezone}** is not a known timezone")),
+            },
+            None => None,
+        };
+        Ok(())
+    }
+    pub fn set_default_lookup(&mut self, category: Option<LookupCategory>) {
+        self.default_lookup = category.map(|c| c.to_string());
+    }
+    pub fn set_ephemeral(&mut self, ephemeral: bool) {
+        self.ephemeral = ephemeral;
+    }
+    pub fn set_evaluation_mode(&mut self, mode: Option<EvaluationMode>) {
+        self.evaluation_mode = mode.map(|m| m.to_string());
+    }
+    pub fn set_log_channel(&mut self, channel: Option<&GuildChannel>) -> Result<(), String> {
+        if let Some(channel) = channel {
+            if !matches!(channel.kind, ChannelType::Text | ChannelType::News) {
+                return Err(format!("<#{}> is not a text channel", channel.id.0));
+            }
+        }
+        self.log_channel_id = channel.map(|c| c.id.0 as i64);
+        Ok(())
+    }
+}
+/// A guild's config, the defaults when it never changed anything.
+pub async fn get_guild_config(guild_id: i64, pool: &PgPool) -> Result<GuildConfig> {
+    let config = query_as!(
+        GuildConfig,
+        "select guild_id, prefix, timezone, default_lookup, ephemeral, evaluation_mode, log_channel_id
+from guild_config where guild_id = $1",
+        guild_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(config.unwrap_or_else(|| GuildConfig::new(guild_id)))
+}
+/// Stores every option of `config`, creating the guild's `guild_settings` row if needed.
+pub async fn save_guild_config(config: &GuildConfig, guild_name: &str, pool: &PgPool) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    query!(
+        "INSERT INTO guild_settings (guild_id, guild_name) VALUES ($1, $2) ON CONFLICT (guild_id) DO NOTHING",
+        config.guild_id,
+        guild_name
+    )
+    .execute(&mut *transaction)
+    .await?;
+    upsert_guild_config(config, &mut transaction).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+/// Stores every option of `config` for a guild that already has a `guild_settings` row.
+pub(crate) async fn upsert_guild_config(config: &GuildConfig, conn: &mut PgConnection) -> Result<()> {
+    query!(
+        "INSERT INTO guild_config (guild_id, prefix, timezone, default_lookup, ephemeral, evaluation_mode, log_channel_id)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT (guild_id)
+DO UPDATE SET
+    prefix = EXCLUDED.prefix,
+    timezone = EXCLUDED.timezone,
+    default_lookup = EXCLUDED.default_lookup,
+    ephemeral = EXCLUDED.ephemeral,
+    evaluation_mode = EXCLUDED.evaluation_mode,
+    log_channel_id = EXCLUDED.log_channel_id",
+        config.guild_id,
+        config.prefix,
+        config.timezone,
+        config.default_lookup,
+        config.ephemeral,
+        config.evaluation_mode,
+        config.log_channel_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+/// Guild configs cached in [`crate::Data`], read on every command and prefix message.
+/// The lock is never held across an await so [`GuildConfigCache::cached`] can be used from sync callbacks.
+/// Clones share the same cache.
+#[derive(Default, Clone)]
+pub struct GuildConfigCache {
+    inner: Arc<RwLock<HashMap<i64, Arc<GuildConfig>>>>,
+}
+impl GuildConfigCache {
+    /// Cached [`get_guild_config`]
+    pub async fn get(&self, guild_id: i64, pool: &PgPool) -> Result<Arc<GuildConfig>> {
+        if let Some(config) = self.cached(guild_id) {
+            return Ok(config);
+        }
+        let config = Arc::new(get_guild_config(guild_id, pool).await?);
+        self.inner
+            .write()
+            .expect("guild config cache poisoned")
+            .insert(guild_id, Arc::clone(&config));
+        Ok(config)
+    }
+    /// The config if it's already loaded, without touching the database.
+    pub fn cached(&self, guild_id: i64) -> Option<Arc<GuildConfig>> {
+        self.inner
+            .read()
+            .expect("guild config cache poisoned")
+            .get(&guild_id)
+            .cloned()
+    }
+    pub fn invalidate(&self, guild_id: i64) {
+        self.inner
+            .write()
+            .expect("guild config cache poisoned")
+            .remove(&guild_id);
+    }
+}
+/// Loads the invoking guild's config before a command runs so [`apply_reply_settings`] can see it.
+pub async fn load_command_config(ctx: Context<'_>) {
+    if let Some(guild_id) = ctx.guild_id() {
+        if let Err(why) = ctx.data().guild_config().get(guild_id.0 as i64, ctx.data().db()).await {
+            log::error!("Failed loading config for guild {}: {why}", guild_id.0);
+        }
+    }
+}
+/// Makes every reply ephemeral in guilds that turned on ephemeral replies.
+pub fn apply_reply_settings(ctx: Context<'_>, reply: &mut poise::CreateReply<'_>) {
+    let ephemeral = ctx
+        .guild_id()
+        .and_then(|id| ctx.data().guild_config().cached(id.0 as i64))
+        .is_some_and(|config| config.ephemeral);
+    if ephemeral {
+        reply.ephemeral(true);
+    }
+}
+/// The guild's prefix override, otherwise the prefix from `COMMAND_PREFIX`.
+pub async fn guild_prefix(
+    ctx: poise::PartialContext<'_, Data, Error>,
+) -> Result<Option<String>, Error> {
+    let prefix = match ctx.guild_id {
+        Some(guild_id) => ctx
+            .data
+            .guild_config()
+            .get(guild_id.0 as i64, ctx.data.db())
+            .await?
+            .prefix
+            .clone(),
+        None => None,
+    };
+    Ok(Some(prefix.unwrap_or_else(|| ctx.data.prefix().to_string())))
+}
+/// Posts to the guild's log channel if it has one.
+pub async fn send_guild_log(
+    http: impl AsRef<Http>,
+    config: &GuildConfig,
+    title: &str,
+    description: String,
+) -> Result<()> {
+    if let Some(channel_id) = config.log_channel_id {
+        ChannelId(channel_id as u64)
+            .send_message(http, |m| {
+                m.embed(|e| e.title(title).description(description).color(Color::DARK_GOLD))
+            })
+            .await?;
+    }
+    Ok(())
+}
+/// [`send_guild_log`] for changes that are already saved, so a broken log channel
+/// is only logged instead of failing the command.
+pub async fn send_guild_log_or_warn(
+    http: impl AsRef<Http>,
+    config: &GuildConfig,
+    title: &str,
+    description: String,
+) {
+    if let Err(why) = send_guild_log(http, config, title, description).await {
+        warn!("Failed posting \"{title}\" log in guild {}: {why}", config.guild_id);
+    }
+}
+async fn autocomplete_timezone(
+    _ctx: Context<'_>,
+    partial: &str,
+) -> Vec<poise::AutocompleteChoice<String>> {
+    let partial = partial.to_lowercase();
+    TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter(|name| name.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|name| poise::AutocompleteChoice {
+            name: name.to_string(),
+            value: name.to_string(),
+        })
+        .collect()
+}
+/// Stores the changed config, shows it and reports the change to the log channel.
+async fn update_config(
+    ctx: Context<'_>,
+    config: GuildConfig,
+    change: Result<String, String>,
+) -> Result<(), Error> {
+    let change = match change {
+        Ok(change) => change,
+        Err(why) => return Ok(embeds::invalid_setting_embed(ctx, why).await?),
+    };
+    let guild_name = ctx.guild().map(|g| g.name).unwrap_or_default();
+    let cache = ctx.data().guild_config();
+    let old = get_guild_config(config.guild_id, ctx.data().db()).await?;
+    save_guild_config(&config, &guild_name, ctx.data().db()).await?;
+    cache.invalidate(config.guild_id);
+    let (before, after) = config.changes_from(&old);
+    if !after.is_empty() {
+        audit_change(ctx, AuditAction::SettingsChange, Some(before), Some(after)).await?;
+    }
+    embeds::guild_config_embed(ctx, &config).await?;
+    send_guild_log_or_warn(
+        ctx,
+        &config,
+        "Settings Changed",
+        format!("{change}\n*by <@{}>*", ctx.author().id.0),
+    )
+    .await;
+    Ok(())
+}
+async fn current_config(ctx: Context<'_>) -> Result<GuildConfig> {
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    get_guild_config(guild_id, ctx.data().db()).await
+}
+fn describe<T: std::fmt::Display>(option: &str, value: Option<T>) -> String {
+    match value {
+        Some(value) => format!("**{option}** set to `{value}`"),
+        None => format!("**{option}** reset to default"),
+    }
+}
+/// View or change this server's settings
+#[poise::command(
+    slash_command,
+    subcommands(
+        "view",
+        "prefix",
+        "timezone",
+        "lookup_category",
+        "ephemeral",
+        "evaluation_mode",
+        "log_channel"
+    ),
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+/// Show this server's settings
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn view(ctx: Context<'_>) -> Result<(), Error> {
+    let config = current_config(ctx).await?;
+    embeds::guild_config_embed(ctx, &config).await?;
+    Ok(())
+}
+/// Set the prefix for text commands, leave empty to use the default
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn prefix(
+    ctx: Context<'_>,
+    #[description = "new prefix"] prefix: Option<String>,
+) -> Result<(), Error> {
+    let mut config = current_config(ctx).await?;
+    let change = config
+        .set_prefix(prefix.clone())
+        .map(|_| describe("Prefix", prefix));
+    update_config(ctx, config, change).await
+}
+/// Set the timezone times are shown in, leave empty to use server time
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn timezone(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_timezone"]
+    #[description = "IANA timezone e.g. America/New_York"]
+    timezone: Option<String>,
+) -> Result<(), Error> {
+    let mut config = current_config(ctx).await?;
+    let change = config
+        .set_timezone(timezone.as_deref())
+        .map(|_| describe("Timezone", config.timezone()));
+    update_config(ctx, config, change).await
+}
+/// Set the category /lookup_df_character uses when none is given
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn lookup_category(
+    ctx: Context<'_>,
+    #[description = "default lookup category"] category: Option<LookupCategory>,
+) -> Result<(), Error> {
+    let mut config = current_config(ctx).await?;
+    let change = describe("Default Lookup", category.as_ref());
+    config.set_default_lookup(category);
+    update_config(ctx, config, Ok(change)).await
+}
+/// Only show command replies to whoever used the command
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn ephemeral(
+    ctx: Context<'_>,
+    #[description = "make replies ephemeral"] enabled: bool,
+) -> Result<(), Error> {
+    let mut config = current_config(ctx).await?;
+    config.set_ephemeral(enabled);
+    update_config(ctx, config, Ok(describe("Ephemeral Replies", Some(enabled)))).await
+}
+/// Choose whether role lookups hide roles that are prereqs of an earned role
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn evaluation_mode(
+    ctx: Context<'_>,
+    #[description = "Highest hides prereqs, All shows every earned role"] mode: Option<
+        EvaluationMode,
+    >,
+) -> Result<(), Error> {
+    let mut config = current_config(ctx).await?;
+    let change = describe("Evaluation Mode", mode.as_ref());
+    config.set_evaluation_mode(mode);
+    update_config(ctx, config, Ok(change)).await
+}
+/// Set the channel admin logs are posted to, leave empty to turn logging off
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn log_channel(
+    ctx: Context<'_>,
+    #[description = "channel for admin logs"] channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let mut config = current_config(ctx).await?;
+    let change = config
+        .set_log_channel(channel.as_ref())
+        .map(|_| describe("Log Channel", channel.as_ref().map(|c| format!("#{}", c.name))));
+    update_config(ctx, config, change).await
+}
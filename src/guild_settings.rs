@@ -1,8 +1,11 @@
+use crate::audit::{audit_change, audit_global_change, record_audit, AuditAction};
 use crate::dev_tools::is_superuser_check;
 use crate::{
-    db::{ASCEND_GUILD_ID, INN_GUILD_ID},
     embeds,
-    requirements::{get_requirements_bytes, RequirementList},
+    requirement_lists::{
+        requirement_count, upsert_list, ASCENDANCIES_LIST, INN_COLLECTION, INN_LIST, ROLES_LIST,
+    },
+    requirements::{get_requirements_bytes, RequirementCache, RequirementList},
     Context, Error,
 };
 use color_eyre::Result;
@@ -11,7 +14,7 @@ use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::Attachment;
 use serde::{Deserialize, Serialize};
 use serenity::model::{channel::Channel, guild::Role};
-use sqlx::{query, query_as, PgPool};
+use sqlx::{query, query_as, PgConnection, PgPool};
 #[derive(sqlx::FromRow, Serialize, Deserialize, Getters, Debug)]
 #[getset(get = "pub")]
 pub struct GuildSettings {
@@ -20,27 +23,77 @@ pub struct GuildSettings {
     pub announcement_channel_id: Option<i64>,
     pub announcement_role_id: Option<i64>,
 }
-//we need to return 'a reference of transaction/pool but we don't need the &requierments
+/// Replaces the guild's own requirement list.
 pub async fn insert_requirements(
     guild_id: i64,
     pool: &PgPool,
+    cache: &RequirementCache,
+    requirements: &RequirementList,
+) -> Result<()> {
+    insert_collection(guild_id, ROLES_LIST, pool, cache, requirements).await
+}
+/// Replaces a named list (e.g. the guild's Inn list) without touching the guild's requirements.
+pub async fn insert_collection(
+    guild_id: i64,
+    collection: &str,
+    pool: &PgPool,
+    cache: &RequirementCache,
     requirements: &RequirementList,
 ) -> Result<()> {
     let mut transaction = pool.begin().await?;
-    // delete any requirements already assigned to guild.
-    query!("delete from requirements where guild_id = $1", guild_id)
+    let list_id = upsert_list(&mut transaction, Some(guild_id), collection, None, None).await?;
+    insert_requirement_list(list_id, &mut transaction, requirements).await?;
+    transaction.commit().await?;
+    cache.invalidate_guild(guild_id).await;
+    Ok(())
+}
+/// Replaces a shared list other guilds can subscribe to, creating it if needed.
+pub async fn insert_shared_list(
+    name: &str,
+    owner_id: Option<i64>,
+    description: Option<&str>,
+    pool: &PgPool,
+    cache: &RequirementCache,
+    requirements: &RequirementList,
+) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    let list_id = upsert_list(&mut transaction, None, name, owner_id, description).await?;
+    insert_requirement_list(list_id, &mut transaction, requirements).await?;
+    transaction.commit().await?;
+    cache.invalidate_all().await;
+    Ok(())
+}
+pub(crate) async fn insert_requirement_list(
+    list_id: i32,
+    transaction: &mut PgConnection,
+    requirements: &RequirementList,
+) -> Result<()> {
+    // delete any requirements already in the list.
+    query!("delete from requirements where list_id = $1", list_id)
         .execute(&mut *transaction)
         .await?;
     //insert requirement
     for req in requirements.requirements() {
         let record = sqlx::query_as!(
                 RequirementId,
-                "INSERT INTO requirements (guild_id, name, description, type, amount) VALUES ($1, $2, $3, $4, $5) RETURNING requirementid",
-                guild_id,
+                "INSERT INTO requirements (list_id, name, description, type, amount, start_date, end_date, require_active, points, category, ladder, excluded_items, excluded_tags) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING requirementid",
+                list_id,
                 req.name(),
                 req.description,
                 req.req_type.to_string(),
-                req.amount
+                req.amount,
+                req.start,
+                req.end,
+                req.require_active,
+                req.points,
+                req.category,
+                req.ladder,
+                req.excluded.as_deref().unwrap_or_default(),
+                &req.excluded_tags
+                    .iter()
+                    .flatten()
+                    .map(|tag| tag.to_string())
+                    .collect::<Vec<String>>()
             )
             .fetch_one(&mut *transaction)
             .await?;
@@ -61,8 +114,8 @@ pub async fn insert_requirements(
     for req in requirements.requirements() {
         let record = sqlx::query_as!(
             RequirementId,
-            "select  RequirementId from requirements where guild_id = $1 and name = $2",
-            guild_id,
+            "select  RequirementId from requirements where list_id = $1 and name = $2",
+            list_id,
             req.name
         )
         .fetch_one(&mut *transaction)
@@ -73,8 +126,8 @@ pub async fn insert_requirements(
             for prereq in prereqs {
                 let record = sqlx::query_as!(
                     RequirementId,
-                    "select RequirementId from requirements where guild_id = $1 and name = $2",
-                    guild_id,
+                    "select RequirementId from requirements where list_id = $1 and name = $2",
+                    list_id,
                     prereq
                 )
                 .fetch_one(&mut *transaction)
@@ -84,35 +137,107 @@ pub async fn insert_requirements(
             }
         }
     }
-    transaction.commit().await?;
     Ok(())
 }
 struct RequirementId {
     requirementid: i32,
 }
-async fn set_requirements(
+/// Downloads and parses an uploaded requirement list.
+/// Replies with an error embed and returns `None` when the file can't be parsed.
+async fn read_requirements(
     ctx: Context<'_>,
     file: Attachment,
-    guild_id: i64,
-    title: String,
-) -> Result<()> {
+) -> Result<Option<RequirementList>> {
     if let Some(file_type) = &file.content_type {
-        if file_type != "application/json; charset=utf-8" {
-            embeds::wrong_file_type(ctx, file_type).await?
+        // discord may or may not add a charset to the content type
+        let mime = file_type.split(';').next().unwrap_or_default().trim();
+        if mime != "application/json" {
+            embeds::wrong_file_type(ctx, file_type).await?;
+            return Ok(None);
         }
     }
     let file = file.download().await?;
-    let mut requirements = match get_requirements_bytes(&file) {
-        Ok(data) => data,
-        Err(e) => return embeds::role_init_error(ctx, e).await
+    match get_requirements_bytes(&file) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) => {
+            embeds::role_init_error(ctx, e).await?;
+            Ok(None)
+        }
+    }
+}
+async fn set_requirements(
+    ctx: Context<'_>,
+    file: Attachment,
+    guild_id: i64,
+    collection: &str,
+    title: String,
+) -> Result<()> {
+    let requirements = match read_requirements(ctx, file).await? {
+        Some(requirements) => requirements,
+        None => return Ok(()),
     };
     let pool = &ctx.data().db_connection;
-    insert_requirements(guild_id, pool, &requirements).await?;
-    embeds::roles_embed(ctx, &mut requirements, title).await?;
+    let before = requirement_count(Some(guild_id), collection, pool).await?;
+    insert_collection(guild_id, collection, pool, ctx.data().requirement_cache(), &requirements).await?;
+    audit_requirement_upload(ctx, collection, before, &requirements, false).await?;
+    embeds::requirements_stored_embed(ctx, title, &requirements).await?;
     Ok(())
 }
-#[poise::command(prefix_command, required_permissions = "ADMINISTRATOR", guild_only)]
-pub async fn set_roles(ctx: Context<'_>, file: Attachment) -> Result<(), Error> {
+pub(crate) async fn set_shared_requirements(
+    ctx: Context<'_>,
+    file: Attachment,
+    name: &str,
+    description: Option<&str>,
+) -> Result<()> {
+    let requirements = match read_requirements(ctx, file).await? {
+        Some(requirements) => requirements,
+        None => return Ok(()),
+    };
+    let owner_id = ctx.author().id.0 as i64;
+    let data = ctx.data();
+    let before = requirement_count(None, name, data.db()).await?;
+    insert_shared_list(
+        name,
+        Some(owner_id),
+        description,
+        data.db(),
+        data.requirement_cache(),
+        &requirements,
+    )
+    .await?;
+    audit_requirement_upload(ctx, name, before, &requirements, true).await?;
+    embeds::requirements_stored_embed(ctx, name.to_string(), &requirements).await?;
+    Ok(())
+}
+/// Shared lists are used by every guild, so their uploads are audited bot-wide.
+async fn audit_requirement_upload(
+    ctx: Context<'_>,
+    name: &str,
+    before: i64,
+    requirements: &RequirementList,
+    shared: bool,
+) -> Result<()> {
+    let count = requirements.requirements().len();
+    let before = Some(format!("{name}: {before} requirements"));
+    let after = Some(format!("{name}: {count} requirements"));
+    if shared {
+        audit_global_change(ctx, AuditAction::RequirementUpload, before, after).await
+    } else {
+        audit_change(ctx, AuditAction::RequirementUpload, before, after).await
+    }
+}
+/// Set this guild's roles from a JSON file
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn set_roles(
+    ctx: Context<'_>,
+    #[description = "JSON list of requirements"] file: Attachment,
+) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap().0 as i64;
     let guild_name = ctx.guild().unwrap().name;
     query!(
@@ -126,42 +251,114 @@ DO NOTHING",
     .execute(ctx.data().db())
     .await?;
     let title = format!("{}'s Roles", ctx.guild().unwrap().name);
-    set_requirements(ctx, file, guild_id, title).await?;
+    set_requirements(ctx, file, guild_id, ROLES_LIST, title).await?;
     Ok(())
 }
+/// Set the shared Ascendancies list from a JSON file
 #[poise::command(
     prefix_command,
+    slash_command,
     required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
     guild_only,
     check = "is_superuser_check"
 )]
-pub async fn set_ascends(ctx: Context<'_>, file: Attachment) -> Result<(), Error> {
-    set_requirements(ctx, file, ASCEND_GUILD_ID, "Ascendancies".to_string()).await?;
+pub async fn set_ascends(
+    ctx: Context<'_>,
+    #[description = "JSON list of requirements"] file: Attachment,
+) -> Result<(), Error> {
+    set_shared_requirements(ctx, file, ASCENDANCIES_LIST, None).await?;
     Ok(())
 }
 
+/// Set this guild's Inn list, or the global Inn list used by guilds without one
 #[poise::command(
     slash_command,
     required_permissions = "ADMINISTRATOR",
     default_member_permissions = "ADMINISTRATOR",
-    guild_only,
-    check = "is_superuser_check"
+    guild_only
 )]
-pub async fn set_inn_items(ctx: Context<'_>, file: Attachment) -> Result<(), Error> {
-    set_requirements(ctx, file, INN_GUILD_ID, "Inn Items".to_string()).await?;
+pub async fn set_inn_items(
+    ctx: Context<'_>,
+    file: Attachment,
+    #[description = "Set the global Inn list instead of this guild's (super users only)"]
+    global: Option<bool>,
+) -> Result<(), Error> {
+    if global.unwrap_or(false) {
+        if !is_superuser_check(ctx).await? {
+            embeds::superuser_only_embed(ctx).await?;
+            return Ok(());
+        }
+        set_shared_requirements(ctx, file, INN_LIST, None).await?;
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let guild_name = ctx.guild().unwrap().name;
+    query!(
+        "INSERT INTO guild_settings (guild_id, guild_name)
+VALUES ($1, $2)
+ON CONFLICT (guild_id)
+DO NOTHING",
+        guild_id,
+        guild_name
+    )
+    .execute(ctx.data().db())
+    .await?;
+    let title = format!("{}'s Inn Items", guild_name);
+    set_requirements(ctx, file, guild_id, INN_COLLECTION, title).await?;
     Ok(())
 }
-#[poise::command(prefix_command, required_permissions = "ADMINISTRATOR", guild_only)]
+/// Set the channel and role for Design Note announcements
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
 pub async fn init_announcements(
     ctx: Context<'_>,
-    channel: Channel,
-    role: Role,
+    #[description = "channel announcements are sent to"] channel: Channel,
+    #[description = "role pinged by announcements"] role: Role,
 ) -> Result<(), Error> {
-    let pool = ctx.data().db();
     let guild_id = ctx.guild_id().unwrap().0 as i64;
     let guild_name = ctx.guild().unwrap().name;
-    let channel_id = channel.id().0 as i64;
-    let role_id = role.id.0 as i64;
+    let settings = set_announcements(
+        guild_id,
+        &guild_name,
+        Some(channel.id().0 as i64),
+        Some(role.id.0 as i64),
+        ctx.data().db(),
+    )
+    .await?;
+    audit_change(
+        ctx,
+        AuditAction::AnnouncementChange,
+        None,
+        Some(format!("Announcements in <#{}> pinging <@&{}>", channel.id().0, role.id.0)),
+    )
+    .await?;
+    embeds::guild_settings_embed(ctx, &settings).await?;
+    Ok(())
+}
+/// Stores where a guild's announcements go and who they ping.
+/// The channel replaces the guild's previous one in its announcement targets.
+pub async fn set_announcements(
+    guild_id: i64,
+    guild_name: &str,
+    channel_id: Option<i64>,
+    role_id: Option<i64>,
+    pool: &PgPool,
+) -> Result<GuildSettings> {
+    let mut transaction = pool.begin().await?;
+    query!(
+        "DELETE FROM announcement_targets WHERE guild_id = $1 and channel_id IS DISTINCT FROM $2
+and channel_id = (select announcement_channel_id from guild_settings where guild_id = $1)",
+        guild_id,
+        channel_id
+    )
+    .execute(&mut *transaction)
+    .await?;
     let settings = query_as!(
         GuildSettings,
         "
@@ -171,22 +368,38 @@ ON CONFLICT (guild_id)
 DO UPDATE SET 
     guild_name = EXCLUDED.guild_name,
     announcement_role_id = EXCLUDED.announcement_role_id,
-    announcement_channel_id = EXCLUDED.announcement_channel_id RETURNING *;
+    announcement_channel_id = EXCLUDED.announcement_channel_id
+RETURNING guild_name, guild_id, announcement_channel_id, announcement_role_id;
     ",
         guild_id,
         guild_name,
         channel_id,
         role_id
     )
-    .fetch_one(pool)
-    .await?;
-    ctx.reply(format!(
-        "announcement channel for {} set to {}",settings.guild_name,settings.announcement_channel_id.unwrap_or(-1)
-    ))
+    .fetch_one(&mut *transaction)
     .await?;
-    Ok(())
+    if let Some(channel_id) = channel_id {
+        query!(
+            "INSERT INTO announcement_targets (guild_id, channel_id, role_id) VALUES ($1, $2, $3)
+ON CONFLICT (guild_id, channel_id) DO UPDATE SET role_id = EXCLUDED.role_id",
+            guild_id,
+            channel_id,
+            role_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(settings)
 }
-#[poise::command(prefix_command, required_permissions = "ADMINISTRATOR", guild_only)]
+/// Store this guild's settings so it can use guild features
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
 pub async fn init_guild(ctx: Context<'_>) -> Result<(), Error> {
     let pool = ctx.data().db();
     let guild_id = ctx.guild_id().unwrap().0 as i64;
@@ -199,18 +412,14 @@ VALUES ($1, $2)
 ON CONFLICT (guild_id)
 DO UPDATE SET 
     guild_name = EXCLUDED.guild_name
-    RETURNING *;
+    RETURNING guild_name, guild_id, announcement_channel_id, announcement_role_id;
     ",
         guild_id,
         guild_name,
     )
     .fetch_one(pool)
     .await?;
-    ctx.reply(format!(
-        "Guild Init with Guild ID:{} and Guild Name:{}",
-        guild_settings.guild_id, guild_settings.guild_name
-    ))
-    .await?;
+    embeds::guild_settings_embed(ctx, &guild_settings).await?;
     Ok(())
 }
 #[poise::command(prefix_command, owners_only, dm_only)]
@@ -219,13 +428,23 @@ pub async fn leave_guild(ctx: Context<'_>, guild_id: i64) -> Result<(), Error> {
     query!("DELETE FROM guild_settings where guild_id = $1;", guild_id,)
         .execute(pool)
         .await?;
+    ctx.data().requirement_cache().invalidate_guild(guild_id).await;
+    ctx.data().guild_config().invalidate(guild_id);
+    let author = ctx.author();
+    let audit = |action, after: &str| {
+        let after = Some(after.to_owned());
+        record_audit(pool, Some(guild_id), Some(author.id.0 as i64), &author.name, action, None, after)
+    };
     if let Some(guild) =
         serenity::GuildId(guild_id as u64).to_guild_cached(&ctx.serenity_context().cache)
     {
         guild.leave(ctx).await?;
+        audit(AuditAction::GuildLeave, "Left the guild and deleted its settings").await?;
         ctx.say(format!("Successfully left the guild: {}", guild.name))
             .await?;
     } else {
+        // the bot never left, only its stored settings are gone
+        audit(AuditAction::SettingsChange, "Deleted the guild's settings").await?;
         ctx.say("Guild not found or bot is not a member of that guild.")
             .await?;
     }
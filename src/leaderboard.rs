@@ -0,0 +1,110 @@
+use crate::embeds::guild_only_with_id;
+use crate::paginate::{paginate, paginate_item, PaginateEmbed};
+use crate::parsing::DFCharacterData;
+use crate::requests::CHARPAGE;
+use crate::requirements::{
+    evaluate_requirements, fetch_member_characters, guild_member_ids, load_inn_list,
+    RequirementList,
+};
+use crate::rng::random_rgb;
+use crate::serenity::Color;
+use crate::{Context, Error};
+use getset::Getters;
+use std::collections::{HashMap, HashSet};
+
+#[derive(poise::ChoiceParameter, PartialEq, Debug)]
+pub enum LeaderboardRanking {
+    Characters,
+    Members,
+}
+/// A character's achievement score.
+#[derive(Getters, Debug)]
+#[getset(get = "pub")]
+pub struct LeaderboardEntry {
+    discord_id: i64,
+    df_id: i32,
+    name: String,
+    score: i32,
+    unique_item_count: u16,
+}
+/// Scores every character, highest first with ties broken by unique item count.
+pub fn rank_characters(
+    roles: &RequirementList,
+    members: &HashMap<i64, Vec<DFCharacterData>>,
+    inn_list: Option<&RequirementList>,
+) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = members
+        .iter()
+        .flat_map(|(discord_id, chars)| {
+            chars.iter().map(|char| LeaderboardEntry {
+                discord_id: *discord_id,
+                df_id: char.id,
+                name: char.name.clone(),
+                score: evaluate_requirements(roles, char, inn_list).score(),
+                unique_item_count: char.unique_item_count,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.unique_item_count.cmp(&a.unique_item_count))
+    });
+    entries
+}
+/// Keeps each member's best character, a member's score is the score of that character.
+pub fn rank_members(entries: Vec<LeaderboardEntry>) -> Vec<LeaderboardEntry> {
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .filter(|entry| seen.insert(entry.discord_id))
+        .collect()
+}
+/// Rank this server's registered characters by achievement score
+#[poise::command(slash_command, guild_only)]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "rank characters or members, defaults to characters"] ranking: Option<
+        LeaderboardRanking,
+    >,
+) -> Result<(), Error> {
+    let guild_id = match guild_only_with_id(ctx).await? {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    ctx.defer().await?;
+    let pool = ctx.data().db();
+    let guild_name = ctx.guild().unwrap().name;
+    let member_ids = guild_member_ids(ctx, guild_id).await?;
+    let members = fetch_member_characters(&member_ids, pool).await?;
+    let cache = ctx.data().requirement_cache();
+    let roles = cache.requirements(guild_id, pool).await?;
+    let inn_list = load_inn_list(&roles, Some(guild_id), pool, cache).await?;
+    let mut entries = rank_characters(&roles, &members, inn_list.as_deref());
+    if ranking == Some(LeaderboardRanking::Members) {
+        entries = rank_members(entries);
+    }
+    let mut pages = vec![String::new()];
+    let (mut current_len, mut current_page) = (0, 0);
+    for (i, entry) in entries.iter().enumerate() {
+        let line = format!(
+            "`#{}` [{}]({CHARPAGE}{}) <@{}>\n**{}** points, {} unique items\n",
+            i + 1,
+            entry.name,
+            entry.df_id,
+            entry.discord_id,
+            entry.score,
+            entry.unique_item_count
+        );
+        paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+    }
+    if pages.last().is_some_and(|p| p.is_empty()) {
+        pages.pop();
+    }
+    let title = format!("{guild_name}'s Leaderboard");
+    let (r, g, b) = random_rgb();
+    let embed = PaginateEmbed::new(&title, None, Color::from_rgb(r, g, b), pages)
+        .set_empty_string("No registered characters to rank");
+    paginate(ctx, embed).await?;
+    Ok(())
+}
@@ -1,14 +1,24 @@
+pub mod announcements;
+pub mod audit;
+pub mod backup;
+pub mod bulk_import;
 pub mod db;
 pub mod dev_tools;
 pub mod embeds;
 pub mod error_handler;
 pub mod event_handler;
+pub mod guild_config;
 pub mod guild_settings;
+pub mod leaderboard;
 pub mod lookup_df;
 pub mod manage_users;
 pub mod mech_aqw_lookup;
+pub mod name_refresh;
+pub mod onboarding;
 pub mod parsing;
+pub mod registration;
 pub mod requests;
+pub mod requirement_lists;
 pub mod requirements;
 pub mod rng;
 pub mod sheets;
@@ -22,6 +32,8 @@ pub use poise::serenity_prelude as serenity;
 pub mod paginate;
 pub mod update_checker;
 pub mod challenge;
+use crate::guild_config::GuildConfigCache;
+use crate::requirements::RequirementCache;
 use crate::serenity::Mutex;
 use color_eyre::owo_colors::{OwoColorize, Rgb, Style};
 use rand::{rngs::ThreadRng, Rng};
@@ -37,6 +49,7 @@ pub fn str_to_i64(s: &str) -> i64 {
     s.chars().map(|c| c as i64).sum()
 }
 
+#[derive(Clone)]
 pub struct Tasks {
     inner: Arc<Mutex<HashMap<String, bool>>>,
 }
@@ -83,22 +96,45 @@ impl Tasks {
     }
 }
 
+/// Cheap to clone, clones share the pool, tasks and caches.
+#[derive(Clone)]
 pub struct Data {
     pub start_time: Instant,
     pub db_connection: PgPool,
     pub tasks: Tasks,
     pub super_users: Vec<u64>,
+    pub requirement_cache: RequirementCache,
+    pub guild_config: GuildConfigCache,
+    /// Prefix from `COMMAND_PREFIX`, used in guilds without a prefix override.
+    pub prefix: String,
 }
 impl Data {
     pub fn tasks(&self) -> &Tasks {
         &self.tasks
     }
-    pub fn new(start_time: Instant, db_connection: PgPool, super_users: Vec<u64>) -> Data {
+    pub fn requirement_cache(&self) -> &RequirementCache {
+        &self.requirement_cache
+    }
+    pub fn guild_config(&self) -> &GuildConfigCache {
+        &self.guild_config
+    }
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+    pub fn new(
+        start_time: Instant,
+        db_connection: PgPool,
+        super_users: Vec<u64>,
+        prefix: String,
+    ) -> Data {
         Data {
             start_time,
             db_connection,
             tasks: Tasks::default(),
             super_users,
+            requirement_cache: RequirementCache::default(),
+            guild_config: GuildConfigCache::default(),
+            prefix,
         }
     }
     pub fn db(&self) -> &PgPool {
@@ -106,7 +142,7 @@ impl Data {
     }
 }
 pub fn get_command_list(
-) -> Vec<poise::Command<Data, Box<(dyn std::error::Error + std::marker::Send + Sync + 'static)>>> {
+) -> Vec<poise::Command<Data, Box<dyn std::error::Error + std::marker::Send + Sync + 'static>>> {
     vec![
         crate::wiki::wiki(),
         crate::time::ping(),
@@ -117,8 +153,14 @@ pub fn get_command_list(
         crate::dev_tools::clear_guild_slash_commands(),
         crate::manage_users::register_character(),
         crate::manage_users::delete_character(),
+        crate::manage_users::transfer_character(),
+        crate::bulk_import::import_registrations(),
+        crate::manage_users::main_character(),
+        crate::manage_users::move_character(),
+        crate::registration::request_registration(),
         crate::lookup_df::lookup_df_character(),
         crate::lookup_df::compare_df_characters(),
+        crate::lookup_df::profile(),
         crate::lookup_df::roles_list(),
         crate::mech_aqw_lookup::lookup_mechquest_id(),
         crate::mech_aqw_lookup::lookup_aqw_character(),
@@ -129,7 +171,19 @@ pub fn get_command_list(
         crate::guild_settings::init_guild(),
         crate::guild_settings::init_announcements(),
         crate::guild_settings::set_inn_items(),
+        crate::guild_config::settings(),
+        crate::announcements::announcements(),
+        crate::backup::backup(),
+        crate::backup::restore(),
+        crate::audit::audit(),
         crate::requirements::inn_items(),
+        crate::requirements::role_stats(),
+        crate::leaderboard::leaderboard(),
+        crate::requirement_lists::shared_lists(),
+        crate::requirement_lists::subscribe_list(),
+        crate::requirement_lists::unsubscribe_list(),
+        crate::requirement_lists::set_shared_list(),
+        crate::requirement_lists::delete_shared_list(),
         crate::update_checker::update_checker(),
     ]
 }
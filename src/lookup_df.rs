@@ -1,8 +1,8 @@
 use crate::db::query_with_id;
 use crate::embeds::*;
-use crate::manage_users::autocomplete_character;
-use crate::parsing::{CharacterFetcher, DFCharacterData, ParsingCategory, WarList};
-use crate::requirements::{get_requirements, RequirementListType};
+use crate::manage_users::{autocomplete_character, get_user_characters};
+use crate::parsing::{fetch_characters_with_items, CharacterFetcher, DFCharacterData, WarList};
+use crate::requirements::{evaluate_member_requirements, load_inn_list, RequirementListType};
 use crate::sheets::compare_sheet;
 use crate::{Context, Error};
 use color_eyre::{eyre::eyre, Result};
@@ -98,7 +98,16 @@ pub async fn lookup_df_character(
     category: Option<LookupCategory>,
 ) -> Result<(), Error> {
     let pool = &ctx.data().db_connection;
-    let category = category.unwrap_or(LookupCategory::CharacterPage);
+    let category = match (category, ctx.guild_id()) {
+        (Some(category), _) => category,
+        (None, Some(guild_id)) => ctx
+            .data()
+            .guild_config()
+            .get(guild_id.0 as i64, pool)
+            .await?
+            .default_lookup(),
+        (None, None) => LookupCategory::CharacterPage,
+    };
     if category == LookupCategory::Roles && guild_only(ctx).await? {
         return Ok(());
     }
@@ -117,8 +126,38 @@ pub async fn lookup_df_character(
         .to_lookupstate()?;
     Ok(send_embed(lookupstate, ctx, df_id).await?)
 }
-/// Compare two DF Characters in various ways
+/// Show every character registered to a user
+#[poise::command(slash_command)]
+pub async fn profile(
+    ctx: Context<'_>,
+    #[description = "User to show the profile of"] user: Option<User>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let pool = ctx.data().db();
+    let user = user.unwrap_or_else(|| ctx.author().clone());
+    let registered = get_user_characters(user.id.0 as i64, pool).await?;
+    if registered.is_empty() {
+        return Ok(no_character_embed(ctx).await?);
+    }
+    let df_ids: Vec<i32> = registered.iter().map(|c| *c.df_id()).collect();
+    let chars = fetch_characters_with_items(&df_ids).await;
+    let roles = match ctx.guild_id() {
+        Some(guild_id) => {
+            let guild_id = guild_id.0 as i64;
+            let cache = ctx.data().requirement_cache();
+            let list = cache.requirements(guild_id, pool).await?;
+            let inn_list = load_inn_list(&list, Some(guild_id), pool, cache).await?;
+            let mode = ctx.data().guild_config().get(guild_id, pool).await?.evaluation_mode();
+            Some(evaluate_member_requirements(&list, &chars, inn_list.as_deref(), mode))
+        }
+        None => None,
+    };
+    send_profile_embed(ctx, &user, &registered, &chars, roles).await?;
+    Ok(())
+}
+/// Compare the stats and items of 2 to 10 DF Characters
 #[poise::command(slash_command)]
+#[allow(clippy::too_many_arguments)]
 pub async fn compare_df_characters(
     ctx: Context<'_>,
     #[autocomplete = "autocomplete_character"]
@@ -127,36 +166,70 @@ pub async fn compare_df_characters(
     #[autocomplete = "autocomplete_character"]
     #[description = "character of selected user"]
     character2: i32,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character3: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character4: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character5: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character6: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character7: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character8: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character9: Option<i32>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "another character to compare"]
+    character10: Option<i32>,
 ) -> Result<(), Error> {
-    let main_state = CharacterFetcher::new(character1, LookupCategory::CharacterPage)
-        .category(ParsingCategory::Items)
-        .fetch_data()
-        .await?
-        .to_lookupstate()?;
-    let second_state = CharacterFetcher::new(character2, LookupCategory::CharacterPage)
-        .category(ParsingCategory::Items)
-        .fetch_data()
-        .await?
-        .to_lookupstate()?;
-    let mut not_found: Vec<i32> = vec![];
-    match (&main_state, &second_state) {
-        (LookupState::NotFound, LookupState::NotFound) => {
-            not_found.extend(vec![character1, character2]);
-        }
-        (LookupState::NotFound, _) => {
-            not_found.push(character1);
+    let characters = [
+        Some(character1),
+        Some(character2),
+        character3,
+        character4,
+        character5,
+        character6,
+        character7,
+        character8,
+        character9,
+        character10,
+    ];
+    let mut df_ids = Vec::new();
+    for df_id in characters.into_iter().flatten() {
+        if !df_ids.contains(&df_id) {
+            df_ids.push(df_id);
         }
-        (_, LookupState::NotFound) => not_found.push(character2),
-        _ => (),
-    };
-    match not_found.len() {
-        0 => {}
-        _ => compare_not_found_embed(ctx, not_found).await?,
-    };
-    let sheet_data = compare_sheet(main_state, second_state).await?;
-    if let Some(sheet) = sheet_data {
-        send_compare_embed(sheet, ctx).await?
     }
+    if df_ids.len() < 2 {
+        ctx.say("Pick at least two different characters to compare")
+            .await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let mut chars = fetch_characters_with_items(&df_ids).await;
+    // fetches finish in any order
+    chars.sort_by_key(|c| df_ids.iter().position(|id| *id == c.id));
+    let not_found: Vec<i32> = df_ids
+        .into_iter()
+        .filter(|id| !chars.iter().any(|c| c.id == *id))
+        .collect();
+    if !not_found.is_empty() {
+        compare_not_found_embed(ctx, not_found).await?;
+    }
+    if chars.len() < 2 {
+        return Ok(());
+    }
+    let sheet = compare_sheet(&chars)?;
+    send_compare_embed(sheet, ctx).await?;
     Ok(())
 }
 /// Get the DF role list for this server
@@ -169,6 +242,6 @@ pub async fn roles_list(ctx: Context<'_>) -> Result<(), Error> {
         return Ok(());
     };
     let title = format!("{}'s Roles", ctx.guild().unwrap().name);
-    let mut roles = get_requirements(guild_id, pool).await?;
+    let mut roles = (*ctx.data().requirement_cache().requirements(guild_id, pool).await?).clone();
     Ok(roles_embed(ctx, &mut roles, title).await?)
 }
@@ -3,6 +3,7 @@ use crate::serenity::GuildId;
 use achivit_rs::db::establish_connection;
 use achivit_rs::error_handler::on_error;
 use achivit_rs::event_handler::event_handler;
+use achivit_rs::guild_config::{apply_reply_settings, guild_prefix, load_command_config};
 use achivit_rs::{get_command_list, print_banner, Data};
 use color_eyre::Result;
 use dotenv::dotenv;
@@ -46,6 +47,8 @@ async fn main() -> Result<()> {
             Box::pin(event_handler(ctx, event, framework, data))
         },
         on_error: |error| Box::pin(on_error(error)),
+        pre_command: |ctx| Box::pin(load_command_config(ctx)),
+        reply_callback: Some(apply_reply_settings),
         post_command: |ctx| {
             Box::pin(async move {
                 info!(
@@ -56,7 +59,7 @@ async fn main() -> Result<()> {
             })
         },
         prefix_options: poise::PrefixFrameworkOptions {
-            prefix: Some(prefix),
+            dynamic_prefix: Some(|ctx| Box::pin(guild_prefix(ctx))),
             ..Default::default()
         },
         ..Default::default()
@@ -68,7 +71,7 @@ async fn main() -> Result<()> {
             info!("Setting up Poise Framework");
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data::new(start_time, db_connection, super_users))
+                Ok(Data::new(start_time, db_connection, super_users, prefix))
             })
         })
         .options(options)
@@ -1,14 +1,198 @@
-use crate::embeds::not_found_embed;
+use crate::audit::{audit_change, AuditAction};
+use crate::embeds::{self, not_found_embed};
+use crate::guild_config::send_guild_log_or_warn;
 use crate::lookup_df::{LookupCategory, LookupState};
 use crate::parsing::CharacterFetcher;
 use crate::requests::CHARPAGE;
 use crate::{Context, Error};
 use color_eyre::Result;
-use poise::serenity_prelude::User;
-use regex::Regex;
+use getset::Getters;
+use log::error;
+use poise::serenity_prelude::{
+    ButtonStyle, CollectComponentInteraction, CommandDataOption, InteractionResponseType, User,
+    UserId,
+};
 use serenity::utils::Color;
-use sqlx::query;
+use sqlx::{query, query_as, PgConnection, PgPool};
+use std::time::Duration;
+/// How long `/transfer_character` waits for the admin to confirm.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A character registered to a user, in the order they chose.
+#[derive(sqlx::FromRow, Getters, Debug)]
+#[getset(get = "pub")]
+pub struct RegisteredCharacter {
+    df_id: i32,
+    character_name: String,
+    is_main: bool,
+}
+impl RegisteredCharacter {
+    /// The character's line in a registration embed, the main is starred.
+    pub fn embed_line(&self) -> String {
+        let star = if self.is_main { "⭐ " } else { "" };
+        format!(
+            "{star}**DF ID:** {} [{}]({}{})",
+            self.df_id, self.character_name, CHARPAGE, self.df_id
+        )
+    }
+}
+/// A user's characters, main first then alts in their chosen order.
+pub async fn get_user_characters(discord_id: i64, pool: &PgPool) -> Result<Vec<RegisteredCharacter>> {
+    let chars = query_as!(
+        RegisteredCharacter,
+        "SELECT df_id, character_name, is_main FROM df_characters WHERE discord_id = $1
+ORDER BY is_main DESC, position ASC NULLS LAST, created ASC",
+        discord_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chars)
+}
+/// Makes `df_id` the user's main, returns `false` when it isn't registered to them.
+pub async fn set_main_character(discord_id: i64, df_id: i32, pool: &PgPool) -> Result<bool> {
+    let mut transaction = pool.begin().await?;
+    let owned = query!(
+        "SELECT df_id FROM df_characters WHERE discord_id = $1 and df_id = $2",
+        discord_id,
+        df_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .is_some();
+    if !owned {
+        return Ok(false);
+    }
+    // unset the old main first, only one main is allowed per user
+    query!(
+        "UPDATE df_characters SET is_main = FALSE WHERE discord_id = $1 and is_main",
+        discord_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    query!(
+        "UPDATE df_characters SET is_main = TRUE WHERE df_id = $1",
+        df_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+/// Moves `df_id` to `position` (starting at 1) among the user's alts and renumbers the rest,
+/// returns `false` when it isn't registered to them. The main is always listed first and isn't
+/// counted, so moving it changes nothing.
+pub async fn reorder_character(
+    discord_id: i64,
+    df_id: i32,
+    position: usize,
+    pool: &PgPool,
+) -> Result<bool> {
+    // lock the user's characters so a concurrent register, transfer or move can't interleave
+    let mut transaction = pool.begin().await?;
+    let chars = query_as!(
+        RegisteredCharacter,
+        "SELECT df_id, character_name, is_main FROM df_characters WHERE discord_id = $1
+ORDER BY is_main DESC, position ASC NULLS LAST, created ASC FOR UPDATE",
+        discord_id
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    match chars.iter().find(|c| c.df_id == df_id) {
+        Some(c) if c.is_main => return Ok(true),
+        Some(_) => {}
+        None => return Ok(false),
+    }
+    let mut order: Vec<i32> = chars.iter().filter(|c| !c.is_main).map(|c| c.df_id).collect();
+    let index = order.iter().position(|id| *id == df_id).unwrap_or_default();
+    order.remove(index);
+    order.insert(position.saturating_sub(1).min(order.len()), df_id);
+    let positions: Vec<i32> = (1..=order.len() as i32).collect();
+    query!(
+        "UPDATE df_characters c SET position = o.position
+FROM UNNEST($2::INT[], $3::INT[]) AS o(df_id, position)
+WHERE c.df_id = o.df_id and c.discord_id = $1",
+        discord_id,
+        &order,
+        &positions
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(true)
+}
 
+/// Registers a character to a user, creating the user if needed.
+/// Returns `false` when the character is already registered to someone.
+pub async fn insert_registration(
+    pool: &PgPool,
+    discord_id: i64,
+    discord_name: &str,
+    df_id: i32,
+    character_name: &str,
+    registered_by: &str,
+) -> Result<bool> {
+    let mut conn = pool.acquire().await?;
+    insert_registration_in(&mut conn, discord_id, discord_name, df_id, character_name, registered_by).await
+}
+/// [`insert_registration`] on a connection, so many can share one transaction.
+pub async fn insert_registration_in(
+    conn: &mut PgConnection,
+    discord_id: i64,
+    discord_name: &str,
+    df_id: i32,
+    character_name: &str,
+    registered_by: &str,
+) -> Result<bool> {
+    query!("INSERT INTO users (discord_id,discord_name,registered_by) VALUES ($1,$2,$3) ON CONFLICT (discord_id) DO NOTHING",discord_id,discord_name,registered_by).execute(&mut *conn).await?;
+    let res = query!("INSERT INTO df_characters (discord_id,df_id,character_name,registered_by) VALUES ($1,$2,$3,$4) ON CONFLICT (df_id) DO NOTHING",discord_id,df_id,character_name,registered_by).execute(&mut *conn).await?;
+    Ok(res.rows_affected() != 0)
+}
+/// Reassigns a character to `to_id`, creating the user if needed, and records the transfer.
+/// Returns the previous owner, `None` when the character isn't registered.
+/// The character stops being the previous owner's main and goes to the end of the new owner's alts.
+pub async fn transfer_character_to(
+    pool: &PgPool,
+    df_id: i32,
+    to_id: i64,
+    to_name: &str,
+    transferred_by: &str,
+    guild_id: Option<i64>,
+) -> Result<Option<i64>> {
+    let mut transaction = pool.begin().await?;
+    let from_id = match query!(
+        "SELECT discord_id FROM df_characters WHERE df_id = $1 FOR UPDATE",
+        df_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    {
+        Some(owner) => owner.discord_id,
+        None => return Ok(None),
+    };
+    if from_id == to_id {
+        return Ok(Some(from_id));
+    }
+    query!("INSERT INTO users (discord_id,discord_name,registered_by) VALUES ($1,$2,$3) ON CONFLICT (discord_id) DO NOTHING",to_id,to_name,transferred_by).execute(&mut *transaction).await?;
+    query!(
+        "UPDATE df_characters SET discord_id = $2, is_main = FALSE, position = NULL WHERE df_id = $1",
+        df_id,
+        to_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    query!(
+        "INSERT INTO character_transfers (df_id, from_discord_id, to_discord_id, transferred_by, guild_id) VALUES ($1, $2, $3, $4, $5)",
+        df_id,
+        from_id,
+        to_id,
+        transferred_by,
+        guild_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(Some(from_id))
+}
 /// Register DF Character by ID
 #[poise::command(
     slash_command,
@@ -19,7 +203,6 @@ pub async fn register_character(ctx: Context<'_>, mut user: User, df_id: i32) ->
     let pool = &ctx.data().db_connection;
     let author = &ctx.author().name;
     let mut user_id = user.id.0 as i64;
-    query!("INSERT INTO users (discord_id,discord_name,registered_by) VALUES ($1,$2,$3) ON CONFLICT (discord_id) DO NOTHING",user_id,user.name,author).execute(pool).await?;
     let lookupstate = CharacterFetcher::new(df_id, LookupCategory::CharacterPage)
         .fetch_data()
         .await?
@@ -30,9 +213,9 @@ pub async fn register_character(ctx: Context<'_>, mut user: User, df_id: i32) ->
         LookupState::NotFound => return Ok(not_found_embed(ctx, df_id).await?),
         _ => panic!("Unexpected LookupState",),
     };
-    let res = query!("INSERT INTO df_characters (discord_id,df_id,character_name,registered_by) VALUES ($1,$2,$3,$4) ON CONFLICT (df_id) DO NOTHING",user_id,df_id,character,author).execute(pool).await?;
+    let registered = insert_registration(pool, user_id, &user.name, df_id, &character, author).await?;
     let color: Color;
-    let title = if res.rows_affected() == 0 {
+    let title = if !registered {
         color = Color::DARK_RED;
         user_id = query!(
             "SELECT discord_id FROM df_characters WHERE df_id = $1",
@@ -45,29 +228,28 @@ pub async fn register_character(ctx: Context<'_>, mut user: User, df_id: i32) ->
         format!("Already Registered: {}", character)
     } else {
         color = Color::DARK_GOLD;
+        audit_change(
+            ctx,
+            AuditAction::CharacterRegister,
+            None,
+            Some(format!("{character} ({df_id}) registered to <@{user_id}>")),
+        )
+        .await?;
         format!("Successfully Registered: {}", character)
     };
     let username = user.name.to_owned();
     let icon_url = user.face();
-    let chars_list = query!(
-        "SELECT df_id,character_name FROM df_characters WHERE discord_id = $1",
-        user_id
-    )
-    .fetch_all(pool)
-    .await?;
-
-    let chars_string = chars_list
+    let chars_string = get_user_characters(user_id, pool)
+        .await?
         .iter()
-        .map(|c| {
-            format!(
-                "**DF ID:** {} [{}]({}{})",
-                c.df_id, c.character_name, CHARPAGE, c.df_id
-            )
-        })
+        .map(RegisteredCharacter::embed_line)
         .collect::<Vec<String>>()
         .join("\n");
     ctx.send(|f| {
         f.embed(|f| {
+            if !registered {
+                f.footer(|f| f.text("Use /transfer_character to move it to another user"));
+            }
             f.title(title)
                 .url(format!("{}{}", CHARPAGE, df_id))
                 .color(color)
@@ -79,17 +261,6 @@ pub async fn register_character(ctx: Context<'_>, mut user: User, df_id: i32) ->
     Ok(())
 }
 
-fn extract_name_from_invokation_data(input: &str) -> i64 {
-    let re = Regex::new(r"user:(\d+)").unwrap();
-    if let Some(captures) = re.captures(input) {
-        if let Some(number) = captures.get(1) {
-            let user_id: i64 = number.as_str().parse().unwrap();
-            return user_id;
-        }
-    }
-    0
-}
-
 /// Delete DF Character by ID
 #[poise::command(
     slash_command,
@@ -120,12 +291,7 @@ pub async fn delete_character(
     )
     .execute(pool)
     .await?;
-    let chars = query!(
-        "SELECT df_id,character_name FROM df_characters WHERE discord_id = $1 order by created asc",
-        user_id
-    )
-    .fetch_all(pool)
-    .await?;
+    let chars = get_user_characters(user_id, pool).await?;
     let username = user.name.to_owned();
     let icon_url = user.face();
     let (color, title) = if res.rows_affected() == 0 {
@@ -148,6 +314,16 @@ pub async fn delete_character(
         .await?;
         return Ok(());
     } else {
+        audit_change(
+            ctx,
+            AuditAction::CharacterDelete,
+            Some(format!(
+                "{} ({character}) registered to <@{user_id}>",
+                db_character.character_name
+            )),
+            None,
+        )
+        .await?;
         (
             Color::DARK_GOLD,
             format!("Successfully DELETED: {}", db_character.character_name),
@@ -155,12 +331,7 @@ pub async fn delete_character(
     };
     let chars_string = chars
         .iter()
-        .map(|c| {
-            format!(
-                "**DF ID:** {} [{}]({}{})",
-                c.df_id, c.character_name, CHARPAGE, c.df_id
-            )
-        })
+        .map(RegisteredCharacter::embed_line)
         .chain(std::iter::once(format!(
             "~~**DF ID:** {} [{}]({}{})~~",
             db_character.df_id, db_character.character_name, CHARPAGE, db_character.df_id
@@ -180,48 +351,295 @@ pub async fn delete_character(
     Ok(())
 }
 
-pub async fn autocomplete_character(
+/// Choose the character used when you don't pick one
+#[poise::command(slash_command)]
+pub async fn main_character(
     ctx: Context<'_>,
-    partial: &str,
-) -> Vec<poise::AutocompleteChoice<i32>> {
-    let discord_id = extract_name_from_invokation_data(&ctx.invocation_string());
-    let mut ac_choices: Vec<poise::AutocompleteChoice<i32>> = Vec::new();
-    let chars = if discord_id != 0 {
-        let query = match partial.parse::<usize>() {
-    Ok(num) => format!("SELECT df_id, character_name FROM df_characters WHERE discord_id = $1 AND CAST(df_id AS TEXT) LIKE '%{}%' ORDER BY created ASC",num),
-    Err(_) => format!("SELECT df_id, character_name FROM df_characters WHERE discord_id = $1 AND character_name ILIKE '%{}%' ORDER BY created ASC",partial)
-};
-        let res = sqlx::query_as::<_, CharacterQuery>(&query)
-            .bind(discord_id)
-            .bind(partial)
-            .fetch_all(&ctx.data().db_connection)
-            .await;
-        match res {
-            Ok(chars) => chars,
-            Err(_) => return ac_choices,
+    #[autocomplete = "autocomplete_own_character"]
+    #[description = "one of your characters"]
+    character: i32,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let discord_id = ctx.author().id.0 as i64;
+    if !set_main_character(discord_id, character, pool).await? {
+        ctx.say(format!("DF ID {character} is not registered to you")).await?;
+        return Ok(());
+    }
+    let chars = get_user_characters(discord_id, pool).await?;
+    let name = &chars[0].character_name;
+    embeds::character_order_embed(ctx, format!("Main Character: {name}"), &chars).await?;
+    Ok(())
+}
+/// Move one of your characters up or down your list of alts
+#[poise::command(slash_command)]
+pub async fn move_character(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_own_character"]
+    #[description = "one of your characters"]
+    character: i32,
+    #[description = "new place in your list of alts, 1 is the top"]
+    #[min = 1]
+    position: usize,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let discord_id = ctx.author().id.0 as i64;
+    if let Some(main) = get_user_characters(discord_id, pool)
+        .await?
+        .iter()
+        .find(|c| c.is_main && c.df_id == character)
+    {
+        ctx.say(format!("**{}** is your main and is always listed first", main.character_name))
+            .await?;
+        return Ok(());
+    }
+    if !reorder_character(discord_id, character, position, pool).await? {
+        ctx.say(format!("DF ID {character} is not registered to you")).await?;
+        return Ok(());
+    }
+    let chars = get_user_characters(discord_id, pool).await?;
+    let name = chars
+        .iter()
+        .find(|c| c.df_id == character)
+        .map(|c| c.character_name.as_str())
+        .unwrap_or_default();
+    embeds::character_order_embed(ctx, format!("Moved: {name}"), &chars).await?;
+    Ok(())
+}
+/// Move a DF character to another user
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn transfer_character(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_character"]
+    #[description = "character to move"]
+    character: i32,
+    #[description = "user to move the character to"] to: User,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let Some(db_character) = query!(
+        "SELECT discord_id, character_name FROM df_characters WHERE df_id = $1",
+        character
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        ctx.say(format!("DF ID {character} is not registered")).await?;
+        return Ok(());
+    };
+    let to_id = to.id.0 as i64;
+    if db_character.discord_id == to_id {
+        ctx.say(format!("**{}** is already registered to <@{to_id}>", db_character.character_name))
+            .await?;
+        return Ok(());
+    }
+    let name = db_character.character_name;
+    let from_id = db_character.discord_id;
+    let guild_id = ctx.guild_id().unwrap();
+    // admins can only take characters from members of their own server
+    if guild_id.member(ctx, UserId(from_id as u64)).await.is_err() {
+        ctx.say(format!("**{name}** is registered to someone who isn't in this server"))
+            .await?;
+        return Ok(());
+    }
+    let ctx_id = ctx.id();
+    let confirm_id = format!("{ctx_id}confirm");
+    let cancel_id = format!("{ctx_id}cancel");
+    let description = format!("**DF ID:** {character}\n**From:** <@{from_id}>\n**To:** <@{to_id}>");
+    let reply = ctx
+        .send(|f| {
+            f.embed(|f| {
+                f.title(format!("Transfer: {name}?"))
+                    .url(format!("{CHARPAGE}{character}"))
+                    .color(Color::DARK_GOLD)
+                    .description(&description)
+            })
+            .components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id(&confirm_id)
+                            .label("Transfer")
+                            .style(ButtonStyle::Success)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(&cancel_id)
+                            .label("Cancel")
+                            .style(ButtonStyle::Danger)
+                    })
+                })
+            })
+        })
+        .await?;
+    let author_id = ctx.author().id;
+    let press = CollectComponentInteraction::new(ctx)
+        .filter(move |press| {
+            press.data.custom_id.starts_with(&ctx_id.to_string()) && press.user.id == author_id
+        })
+        .timeout(TRANSFER_TIMEOUT)
+        .await;
+    let confirmed = press
+        .as_ref()
+        .is_some_and(|press| press.data.custom_id == confirm_id);
+    // the owner may have changed while waiting for the confirmation
+    let mut transferred_from = None;
+    let (color, outcome) = if confirmed {
+        let author = &ctx.author().name;
+        let transfer =
+            transfer_character_to(pool, character, to_id, &to.name, author, Some(guild_id.0 as i64));
+        match transfer.await? {
+            Some(prev) if prev != to_id => {
+                audit_change(
+                    ctx,
+                    AuditAction::CharacterTransfer,
+                    Some(format!("{name} ({character}) registered to <@{prev}>")),
+                    Some(format!("{name} ({character}) registered to <@{to_id}>")),
+                )
+                .await?;
+                transferred_from = Some(prev);
+                (Color::DARK_GREEN, format!("Transferred by {author}"))
+            }
+            Some(_) => (Color::DARK_RED, format!("The character was already moved to {}", to.name)),
+            None => (Color::DARK_RED, "The character was deleted before the transfer".to_owned()),
         }
     } else {
-        let query = match partial.parse::<usize>() {
-    Ok(num) => format!("SELECT df_id, character_name FROM df_characters WHERE CAST(df_id AS TEXT) LIKE '%{}%' ORDER BY created ASC",num),
-    Err(_) => format!("SELECT df_id, character_name FROM df_characters WHERE character_name ILIKE '%{}%' ORDER BY created ASC",partial)
-};
-        sqlx::query_as::<_, CharacterQuery>(&query)
-            .fetch_all(&ctx.data().db_connection)
-            .await
-            .unwrap()
+        (Color::DARK_RED, "Transfer cancelled".to_owned())
     };
-    ac_choices = chars
-        .iter()
-        .map(|choice| poise::AutocompleteChoice {
-            name: format!("{}: {}", choice.character_name, choice.df_id),
-            value: choice.df_id,
-        })
-        .collect();
-    ac_choices
+    match press {
+        Some(press) => {
+            press
+                .create_interaction_response(ctx, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.embed(|e| {
+                                e.title(format!("Transfer: {name}"))
+                                    .url(format!("{CHARPAGE}{character}"))
+                                    .color(color)
+                                    .description(&description)
+                                    .footer(|f| f.text(&outcome))
+                            })
+                            .components(|c| c)
+                        })
+                })
+                .await?;
+        }
+        None => {
+            reply
+                .edit(ctx, |m| {
+                    m.embed(|e| {
+                        e.title(format!("Transfer: {name}"))
+                            .color(color)
+                            .description(&description)
+                            .footer(|f| f.text(&outcome))
+                    })
+                    .components(|c| c)
+                })
+                .await?;
+        }
+    }
+    if let Some(prev) = transferred_from {
+        let config = ctx.data().guild_config().get(guild_id.0 as i64, pool).await?;
+        send_guild_log_or_warn(
+            ctx,
+            &config,
+            "Character Transferred",
+            format!("[{name}]({CHARPAGE}{character}) from <@{prev}> to <@{to_id}>\n*by <@{}>*", author_id.0),
+        )
+        .await;
+    }
+    Ok(())
 }
+/// Most choices Discord accepts for an autocomplete.
+pub const MAX_AUTOCOMPLETE_CHOICES: i64 = 25;
 
-#[derive(sqlx::FromRow, Debug)]
-struct CharacterQuery {
-    character_name: String,
+/// A registered character offered as an autocomplete choice.
+#[derive(sqlx::FromRow, Getters, Debug)]
+#[getset(get = "pub")]
+pub struct CharacterChoice {
     df_id: i32,
+    character_name: String,
+}
+/// Escapes `LIKE` wildcards so the input only matches literally.
+fn escape_like(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+/// Characters matching `partial`, limited to `discord_id`'s when given.
+/// An exact DF ID comes first, then names by trigram similarity, then the user's order and the newest registrations.
+pub async fn search_characters(
+    discord_id: Option<i64>,
+    partial: &str,
+    pool: &PgPool,
+) -> Result<Vec<CharacterChoice>> {
+    let partial = partial.trim();
+    let chars = query_as!(
+        CharacterChoice,
+        "SELECT df_id, character_name FROM df_characters
+WHERE ($1::BIGINT IS NULL OR discord_id = $1)
+    AND ($2 = ''
+        OR character_name % $2
+        OR $2 <% character_name
+        OR character_name ILIKE '%' || $3 || '%'
+        OR CAST(df_id AS TEXT) LIKE $3 || '%')
+ORDER BY CAST(df_id AS TEXT) = $2 DESC,
+    GREATEST(similarity(character_name, $2), word_similarity($2, character_name)) DESC,
+    is_main DESC, position ASC NULLS LAST, created DESC
+LIMIT $4",
+        discord_id,
+        partial,
+        escape_like(partial),
+        MAX_AUTOCOMPLETE_CHOICES
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chars)
+}
+/// The user picked in a command's `user` option, read from the interaction.
+pub fn selected_user(options: &[CommandDataOption]) -> Option<i64> {
+    options
+        .iter()
+        .find(|option| option.name == "user")?
+        .value
+        .as_ref()?
+        .as_str()?
+        .parse()
+        .ok()
+}
+fn to_choices(chars: Result<Vec<CharacterChoice>>) -> Vec<poise::AutocompleteChoice<i32>> {
+    match chars {
+        Ok(chars) => chars
+            .into_iter()
+            .map(|c| poise::AutocompleteChoice {
+                name: format!("{}: {}", c.character_name, c.df_id),
+                value: c.df_id,
+            })
+            .collect(),
+        Err(why) => {
+            error!("Character autocomplete failed: {why}");
+            Vec::new()
+        }
+    }
+}
+/// Suggests the invoking user's own characters.
+pub async fn autocomplete_own_character(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<poise::AutocompleteChoice<i32>> {
+    let discord_id = ctx.author().id.0 as i64;
+    to_choices(search_characters(Some(discord_id), partial, ctx.data().db()).await)
+}
+/// Suggests the characters of the command's `user` option, or everyone's when it's empty.
+pub async fn autocomplete_character(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<poise::AutocompleteChoice<i32>> {
+    let discord_id = match ctx {
+        poise::Context::Application(actx) => selected_user(actx.args),
+        poise::Context::Prefix(_) => None,
+    };
+    to_choices(search_characters(discord_id, partial, ctx.data().db()).await)
 }
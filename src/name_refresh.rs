@@ -0,0 +1,245 @@
+use crate::guild_config::{get_guild_config, send_guild_log};
+use crate::lookup_df::{LookupCategory, LookupState};
+use crate::parsing::CharacterFetcher;
+use crate::requests::CHARPAGE;
+use crate::serenity::{Context, GuildId, UserId};
+use color_eyre::Result;
+use log::{error, info};
+use sqlx::{query, query_as, PgPool};
+use tokio::time::{self, Duration};
+/// How often a batch of stale characters is refreshed.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Pause between charpage fetches, so a batch doesn't hammer the DF servers.
+const FETCH_DELAY: Duration = Duration::from_secs(3);
+/// Characters refreshed per batch.
+const REFRESH_BATCH: i64 = 50;
+/// Days before a character's name is checked again.
+const STALE_AFTER_DAYS: i32 = 7;
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct StaleCharacter {
+    pub df_id: i32,
+    pub discord_id: i64,
+    pub character_name: String,
+}
+/// What a refresh changed about a character.
+#[derive(PartialEq, Debug)]
+pub enum NameChange {
+    Renamed {
+        old_name: String,
+        new_name: String,
+    },
+    /// The charpage no longer exists.
+    NotFound {
+        name: String,
+    },
+    /// The charpage exists again after being not found.
+    Found {
+        name: String,
+    },
+}
+#[derive(Debug)]
+pub struct RefreshedCharacter {
+    pub df_id: i32,
+    pub discord_id: i64,
+    pub change: NameChange,
+}
+impl RefreshedCharacter {
+    /// The change's line in a guild's log message.
+    pub fn log_line(&self) -> String {
+        let link = format!("{CHARPAGE}{}", self.df_id);
+        let owner = self.discord_id;
+        match &self.change {
+            NameChange::Renamed { old_name, new_name } => {
+                format!("**{old_name}** is now [{new_name}]({link}) *(<@{owner}>)*")
+            }
+            NameChange::NotFound { name } => {
+                format!("[{name}]({link}) was not found *(<@{owner}>)*")
+            }
+            NameChange::Found { name } => {
+                format!("[{name}]({link}) was found again *(<@{owner}>)*")
+            }
+        }
+    }
+}
+/// The characters whose names were checked longest ago, never checked first.
+pub async fn stale_characters(pool: &PgPool, limit: i64) -> Result<Vec<StaleCharacter>> {
+    let chars = query_as!(
+        StaleCharacter,
+        "SELECT df_id, discord_id, character_name FROM df_characters
+WHERE name_checked IS NULL OR name_checked < now() - make_interval(days => $1)
+ORDER BY name_checked ASC NULLS FIRST, created ASC LIMIT $2",
+        STALE_AFTER_DAYS,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chars)
+}
+/// Stores a fetched name, `None` when the charpage wasn't found.
+/// Returns the change when the name or the not found flag changed.
+pub async fn apply_refresh(
+    pool: &PgPool,
+    df_id: i32,
+    fetched_name: Option<&str>,
+) -> Result<Option<RefreshedCharacter>> {
+    let old = match query!(
+        "UPDATE df_characters new SET
+    character_name = COALESCE($2, old.character_name),
+    not_found_since = CASE WHEN $2 IS NULL THEN COALESCE(old.not_found_since, now()) END,
+    name_checked = now()
+FROM df_characters old WHERE new.df_id = $1 and old.df_id = new.df_id
+RETURNING old.discord_id, old.character_name, old.not_found_since IS NOT NULL as \"was_not_found!\"",
+        df_id,
+        fetched_name
+    )
+    .fetch_optional(pool)
+    .await?
+    {
+        Some(old) => old,
+        // deleted since the batch was picked
+        None => return Ok(None),
+    };
+    let change = match fetched_name {
+        None if old.was_not_found => return Ok(None),
+        None => NameChange::NotFound {
+            name: old.character_name,
+        },
+        Some(name) if name != old.character_name => NameChange::Renamed {
+            old_name: old.character_name,
+            new_name: name.to_owned(),
+        },
+        Some(name) if old.was_not_found => NameChange::Found {
+            name: name.to_owned(),
+        },
+        Some(_) => return Ok(None),
+    };
+    Ok(Some(RefreshedCharacter {
+        df_id,
+        discord_id: old.discord_id,
+        change,
+    }))
+}
+/// Marks a character checked without changing its name, for charpages that failed to fetch or parse,
+/// so they go to the back of the queue instead of blocking every later batch.
+pub async fn skip_refresh(pool: &PgPool, df_id: i32) -> Result<()> {
+    query!("UPDATE df_characters SET name_checked = now() WHERE df_id = $1", df_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+/// Fetches one batch of stale characters one at a time and stores their names.
+pub async fn refresh_character_names(pool: &PgPool) -> Result<Vec<RefreshedCharacter>> {
+    let mut changes = Vec::new();
+    for char in stale_characters(pool, REFRESH_BATCH).await? {
+        time::sleep(FETCH_DELAY).await;
+        let lookupstate = CharacterFetcher::new(char.df_id, LookupCategory::CharacterPage)
+            .fetch_data()
+            .await
+            .and_then(|data| data.to_lookupstate());
+        let fetched_name = match lookupstate {
+            Ok(LookupState::CharacterPage(page)) => Some(page.name),
+            Ok(LookupState::FlashCharatcerPage(page)) if page.contains_key("Name") => {
+                page.get("Name").cloned()
+            }
+            Ok(LookupState::NotFound) => None,
+            Ok(_) => {
+                // a page without a name isn't proof the character is gone
+                skip_refresh(pool, char.df_id).await?;
+                continue;
+            }
+            Err(why) => {
+                error!("Failed refreshing character {}: {why}", char.df_id);
+                skip_refresh(pool, char.df_id).await?;
+                continue;
+            }
+        };
+        if let Some(change) = apply_refresh(pool, char.df_id, fetched_name.as_deref()).await? {
+            changes.push(change);
+        }
+    }
+    Ok(changes)
+}
+/// Longest log description sent, embed descriptions are limited to 4096 characters.
+const MAX_LOG_LENGTH: usize = 4000;
+/// Splits log lines into descriptions under [`MAX_LOG_LENGTH`], cutting any single line that doesn't fit.
+pub fn log_descriptions(lines: &[String]) -> Vec<String> {
+    let mut descriptions = Vec::new();
+    let mut description = String::new();
+    for line in lines {
+        let mut line = line.as_str();
+        if line.len() >= MAX_LOG_LENGTH {
+            let end = (0..MAX_LOG_LENGTH).rev().find(|i| line.is_char_boundary(*i)).unwrap_or_default();
+            line = &line[..end];
+        }
+        if !description.is_empty() && description.len() + line.len() >= MAX_LOG_LENGTH {
+            descriptions.push(std::mem::take(&mut description));
+        }
+        description.push_str(line);
+        description.push('\n');
+    }
+    if !description.is_empty() {
+        descriptions.push(description);
+    }
+    descriptions
+}
+/// Posts each change to the log channel of every guild the character's owner is in.
+async fn report_changes(
+    ctx: &Context,
+    pool: &PgPool,
+    changes: &[RefreshedCharacter],
+) -> Result<()> {
+    let guild_ids = query!(
+        "SELECT c.guild_id FROM guild_config c JOIN guild_settings g ON g.guild_id = c.guild_id
+WHERE g.active and c.log_channel_id IS NOT NULL"
+    )
+    .fetch_all(pool)
+    .await?;
+    for guild_id in guild_ids.into_iter().map(|r| r.guild_id) {
+        let lines: Vec<String> = changes
+            .iter()
+            .filter(|c| {
+                ctx.cache
+                    .member(GuildId(guild_id as u64), UserId(c.discord_id as u64))
+                    .is_some()
+            })
+            .map(RefreshedCharacter::log_line)
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let config = get_guild_config(guild_id, pool).await?;
+        // one guild's missing permissions shouldn't keep the rest from their logs
+        for description in log_descriptions(&lines) {
+            let log = send_guild_log(ctx, &config, "Character Names Updated", description);
+            if let Err(why) = log.await {
+                error!("Failed logging name changes in guild {guild_id}: {why}");
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+/// Refreshes a batch of character names every [`REFRESH_INTERVAL`] for as long as the bot runs.
+pub fn spawn_name_refresh(ctx: Context, pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let changes = match refresh_character_names(&pool).await {
+                Ok(changes) => changes,
+                Err(why) => {
+                    error!("Failed refreshing character names: {why}");
+                    continue;
+                }
+            };
+            if changes.is_empty() {
+                continue;
+            }
+            info!("Refreshed {} character names", changes.len());
+            if let Err(why) = report_changes(&ctx, &pool, &changes).await {
+                error!("Failed reporting character name changes: {why}");
+            }
+        }
+    });
+}
@@ -0,0 +1,487 @@
+use crate::guild_settings::{insert_requirements, set_announcements};
+use crate::requirement_lists::{get_list_id, get_shared_lists, insert_subscription};
+use crate::requirements::get_requirements_bytes;
+use crate::serenity::{
+    ActionRowComponent, ButtonStyle, ChannelId, ChannelType, CollectComponentInteraction,
+    CollectModalInteraction, CollectReply, Color, Context, CreateComponents, Guild,
+    InputTextStyle, InteractionResponseType, Message, MessageComponentInteraction, Permissions,
+    RoleId, UserId,
+};
+use crate::Data;
+use color_eyre::Result;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+/// How long the wizard waits on each step before giving up.
+const STEP_TIMEOUT: Duration = Duration::from_secs(60 * 15);
+/// Permissions the bot needs in the announcement channel.
+const ANNOUNCEMENT_PERMISSIONS: [(Permissions, &str); 4] = [
+    (Permissions::VIEW_CHANNEL, "View Channel"),
+    (Permissions::SEND_MESSAGES, "Send Messages"),
+    (Permissions::EMBED_LINKS, "Embed Links"),
+    (Permissions::MENTION_EVERYONE, "Mention Roles"),
+];
+/// Button that asks for an id in a modal.
+const MODAL_BUTTON: &str = "other";
+const SETUP_COMMANDS: &str =
+    "You can set things up later with `/init_announcements`, `/set_roles` and `/subscribe_list`.";
+
+/// Walks a newly joined guild's admins through setting up announcements and requirement lists.
+/// Posted in the system channel when the bot can talk there, otherwise DM'd to the owner.
+pub async fn start_onboarding(ctx: &Context, guild: &Guild, data: &Data) -> Result<()> {
+    let mut wizard = match Wizard::new(ctx, guild, data).await? {
+        Some(wizard) => wizard,
+        None => return Ok(()),
+    };
+    let start = wizard
+        .step(
+            format!("Thanks for adding me to {}!", guild.name),
+            "Let's get this server set up, it only takes a minute.".to_string(),
+            buttons(&wizard.id, &[("start", "Start Setup", ButtonStyle::Success), ("skip", "Skip", ButtonStyle::Secondary)]),
+        )
+        .await?;
+    if start.as_deref() != Some("start") {
+        return wizard.finish("Setup skipped", SETUP_COMMANDS.to_string()).await;
+    }
+    let channel_id = wizard.choose_channel().await?;
+    let role_id = wizard.choose_role().await?;
+    let list = wizard.choose_requirements().await?;
+    let permissions = wizard.check_permissions(channel_id).await;
+    let settings = set_announcements(
+        guild.id.0 as i64,
+        &guild.name,
+        channel_id.map(|id| id.0 as i64),
+        role_id.map(|id| id.0 as i64),
+        data.db(),
+    )
+    .await?;
+    let summary = setup_summary(
+        *settings.announcement_channel_id(),
+        *settings.announcement_role_id(),
+        list.as_deref(),
+        &permissions,
+    );
+    wizard.finish("Setup Complete!", summary).await
+}
+/// The wizard's last step, what was set up and what still can be.
+pub fn setup_summary(
+    channel_id: Option<i64>,
+    role_id: Option<i64>,
+    requirements: Option<&str>,
+    permissions: &str,
+) -> String {
+    let mut summary = String::new();
+    let _ = writeln!(
+        summary,
+        "**Announcement Channel:** {}",
+        channel_id.map_or("*not set*".to_string(), |id| format!("<#{id}>"))
+    );
+    let _ = writeln!(
+        summary,
+        "**Ping Role:** {}",
+        role_id.map_or("*not set*".to_string(), |id| format!("<@&{id}>"))
+    );
+    let _ = writeln!(summary, "**Requirements:** {}", requirements.unwrap_or("*not set*"));
+    let _ = writeln!(summary, "\n{permissions}\n{SETUP_COMMANDS}");
+    summary
+}
+/// A pressed component as the wizard's steps see it, the custom id without the wizard's prefix
+/// followed by `:value` for select menus.
+pub fn parse_choice(wizard_id: &str, custom_id: &str, values: &[String]) -> String {
+    let custom_id = custom_id.trim_start_matches(wizard_id);
+    match values.first() {
+        Some(value) => format!("{custom_id}:{value}"),
+        None => custom_id.to_string(),
+    }
+}
+/// The id picked in the `select` menu of a [`parse_choice`] choice.
+pub fn selected_id(choice: &str, select: &str) -> Option<u64> {
+    choice.strip_prefix(select)?.strip_prefix(':')?.parse().ok()
+}
+/// An id typed into the modal, either the id itself or a mention of it.
+pub fn parse_id(input: &str) -> Option<u64> {
+    let digits: String = input.chars().filter(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+/// Lists which of [`ANNOUNCEMENT_PERMISSIONS`] the bot has, `None` when there is no channel to check.
+pub fn permissions_summary(permissions: Option<Permissions>) -> String {
+    let mut text = String::from("__**Permissions**__\n");
+    match permissions {
+        Some(permissions) => {
+            for (permission, name) in ANNOUNCEMENT_PERMISSIONS {
+                let mark = if permissions.contains(permission) { "✅" } else { "❌" };
+                let _ = writeln!(text, "{mark} {name}");
+            }
+        }
+        None => text.push_str("*No announcement channel to check*\n"),
+    }
+    text
+}
+
+struct Wizard<'a> {
+    ctx: &'a Context,
+    guild: &'a Guild,
+    data: &'a Data,
+    message: Message,
+    /// Prefix of every component id so other messages' components are ignored.
+    id: String,
+    /// Last [`MODAL_BUTTON`] press, which is answered with a modal instead of being acknowledged.
+    pending: Option<Arc<MessageComponentInteraction>>,
+    /// Admin who pressed the last component.
+    user_id: Option<UserId>,
+}
+impl<'a> Wizard<'a> {
+    async fn new(ctx: &'a Context, guild: &'a Guild, data: &'a Data) -> Result<Option<Wizard<'a>>> {
+        let id = format!("onboarding{}", guild.id.0);
+        let bot = guild.member(ctx, ctx.cache.current_user_id()).await?;
+        let system_channel = guild.system_channel_id.and_then(|channel_id| {
+            let channel = guild.channels.get(&channel_id)?.clone().guild()?;
+            let permissions = guild.user_permissions_in(&channel, &bot).ok()?;
+            (permissions.send_messages() && permissions.embed_links()).then_some(channel_id)
+        });
+        let channel_id = match system_channel {
+            Some(channel_id) => channel_id,
+            None => match guild.owner_id.create_dm_channel(ctx).await {
+                Ok(dm) => dm.id,
+                Err(_) => return Ok(None),
+            },
+        };
+        let message = channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| e.title("Achivit Setup").color(Color::DARK_GOLD))
+            })
+            .await?;
+        Ok(Some(Wizard {
+            ctx,
+            guild,
+            data,
+            message,
+            id,
+            pending: None,
+            user_id: None,
+        }))
+    }
+    /// Shows a step and waits for a component on it, returning the pressed button or selected value
+    /// with the wizard's id prefix removed. `None` when the step timed out.
+    async fn step(
+        &mut self,
+        title: String,
+        description: String,
+        components: CreateComponents,
+    ) -> Result<Option<String>> {
+        self.message
+            .edit(self.ctx, |m| {
+                m.embed(|e| e.title(title).description(description).color(Color::DARK_GOLD))
+                    .set_components(components)
+            })
+            .await?;
+        let press = match self.wait_for_component().await {
+            Some(press) => press,
+            None => {
+                self.finish("Setup timed out", SETUP_COMMANDS.to_string()).await?;
+                return Ok(None);
+            }
+        };
+        self.user_id = Some(press.user.id);
+        let choice = parse_choice(&self.id, &press.data.custom_id, &press.data.values);
+        if choice == MODAL_BUTTON {
+            self.pending = Some(press);
+        } else {
+            press
+                .create_interaction_response(self.ctx, |r| {
+                    r.kind(InteractionResponseType::DeferredUpdateMessage)
+                })
+                .await?;
+        }
+        Ok(Some(choice))
+    }
+    async fn wait_for_component(&self) -> Option<Arc<MessageComponentInteraction>> {
+        let id = self.id.clone();
+        CollectComponentInteraction::new(self.ctx)
+            .message_id(self.message.id)
+            // only admins can answer the wizard when it's posted in the guild
+            .filter(move |press| {
+                press.data.custom_id.starts_with(&id)
+                    && press
+                        .member
+                        .as_ref()
+                        .is_none_or(|m| m.permissions.is_some_and(|p| p.administrator()))
+            })
+            .timeout(STEP_TIMEOUT)
+            .await
+    }
+    /// Answers the pending [`MODAL_BUTTON`] press with a modal asking for an id,
+    /// for when what the admin wants isn't in a select menu.
+    async fn ask_id(&mut self, label: &str) -> Result<Option<u64>> {
+        let press = match self.pending.take() {
+            Some(press) => press,
+            None => return Ok(None),
+        };
+        let modal_id = format!("{}modal", self.id);
+        press
+            .create_interaction_response(self.ctx, |r| {
+                r.kind(InteractionResponseType::Modal)
+                    .interaction_response_data(|d| {
+                        d.custom_id(&modal_id).title(label).components(|c| {
+                            c.create_action_row(|row| {
+                                row.create_input_text(|t| {
+                                    t.custom_id("id")
+                                        .label(label)
+                                        .placeholder("ID or mention")
+                                        .style(InputTextStyle::Short)
+                                        .required(true)
+                                })
+                            })
+                        })
+                    })
+            })
+            .await?;
+        let submit = match CollectModalInteraction::new(self.ctx)
+            .author_id(press.user.id)
+            .filter(move |m| m.data.custom_id == modal_id)
+            .timeout(STEP_TIMEOUT)
+            .await
+        {
+            Some(submit) => submit,
+            None => return Ok(None),
+        };
+        submit
+            .create_interaction_response(self.ctx, |r| {
+                r.kind(InteractionResponseType::DeferredUpdateMessage)
+            })
+            .await?;
+        let value = submit
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|c| match c {
+                ActionRowComponent::InputText(input) => Some(input.value.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        Ok(parse_id(&value))
+    }
+    async fn choose_channel(&mut self) -> Result<Option<ChannelId>> {
+        let mut channels: Vec<_> = self
+            .guild
+            .channels
+            .values()
+            .filter_map(|c| c.clone().guild())
+            .filter(|c| matches!(c.kind, ChannelType::Text | ChannelType::News))
+            .collect();
+        channels.sort_by_key(|c| c.position);
+        let options: Vec<(String, String)> = channels
+            .iter()
+            .map(|c| (format!("#{}", c.name), c.id.0.to_string()))
+            .collect();
+        loop {
+            let mut components = select(&self.id, "channel", "Choose a channel", &options);
+            components.add_action_row(button_row(
+                &self.id,
+                &[(MODAL_BUTTON, "Other Channel", ButtonStyle::Primary), ("skip", "Skip", ButtonStyle::Secondary)],
+            ));
+            let choice = self
+                .step(
+                    "Announcement Channel".to_string(),
+                    "Where should DragonFable Design Note announcements be posted?".to_string(),
+                    components,
+                )
+                .await?;
+            let id = match choice.as_deref() {
+                Some(MODAL_BUTTON) => self.ask_id("Channel").await?,
+                Some(choice) => selected_id(choice, "channel"),
+                None => None,
+            };
+            match id.map(ChannelId) {
+                Some(id) if self.guild.channels.contains_key(&id) => return Ok(Some(id)),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+    async fn choose_role(&mut self) -> Result<Option<RoleId>> {
+        let mut roles: Vec<_> = self
+            .guild
+            .roles
+            .values()
+            .filter(|r| !r.managed && r.id.0 != self.guild.id.0)
+            .collect();
+        roles.sort_by_key(|r| std::cmp::Reverse(r.position));
+        let options: Vec<(String, String)> = roles
+            .iter()
+            .map(|r| (format!("@{}", r.name), r.id.0.to_string()))
+            .collect();
+        loop {
+            let mut components = select(&self.id, "role", "Choose a role", &options);
+            components.add_action_row(button_row(
+                &self.id,
+                &[(MODAL_BUTTON, "Other Role", ButtonStyle::Primary), ("skip", "No Ping", ButtonStyle::Secondary)],
+            ));
+            let choice = self
+                .step(
+                    "Ping Role".to_string(),
+                    "Which role should be pinged when an update goes live?".to_string(),
+                    components,
+                )
+                .await?;
+            let id = match choice.as_deref() {
+                Some(MODAL_BUTTON) => self.ask_id("Role").await?,
+                Some(choice) => selected_id(choice, "role"),
+                None => None,
+            };
+            match id.map(RoleId) {
+                Some(id) if self.guild.roles.contains_key(&id) => return Ok(Some(id)),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+    /// Returns a description of the stored requirements.
+    async fn choose_requirements(&mut self) -> Result<Option<String>> {
+        let guild_id = self.guild.id.0 as i64;
+        let pool = self.data.db();
+        let cache = self.data.requirement_cache();
+        let mut description =
+            "Upload a JSON requirement list of your own or subscribe to a shared one.".to_string();
+        loop {
+            let choice = self
+                .step(
+                    "Requirements".to_string(),
+                    description.clone(),
+                    buttons(
+                        &self.id,
+                        &[
+                            ("upload", "Upload List", ButtonStyle::Primary),
+                            ("subscribe", "Shared List", ButtonStyle::Primary),
+                            ("skip", "Skip", ButtonStyle::Secondary),
+                        ],
+                    ),
+                )
+                .await?;
+            match choice.as_deref() {
+                Some("upload") => {
+                    self.message
+                        .edit(self.ctx, |m| {
+                            m.embed(|e| {
+                                e.title("Requirements")
+                                    .description("Send your requirement list as a JSON file in this channel.")
+                                    .color(Color::DARK_GOLD)
+                            })
+                            .set_components(CreateComponents::default())
+                        })
+                        .await?;
+                    let reply = match self.wait_for_upload().await {
+                        Some(reply) => reply,
+                        None => return Ok(None),
+                    };
+                    let attachment = match reply.attachments.first() {
+                        Some(attachment) => attachment,
+                        None => {
+                            description = "That message had no file attached, try again.".to_string();
+                            continue;
+                        }
+                    };
+                    match get_requirements_bytes(&attachment.download().await?) {
+                        Ok(requirements) => {
+                            insert_requirements(guild_id, pool, cache, &requirements).await?;
+                            return Ok(Some(format!("{} uploaded", requirements.requirements().len())));
+                        }
+                        Err(why) => {
+                            description = format!("Couldn't read that file, try again.\n**Error:** {why}");
+                        }
+                    }
+                }
+                Some("subscribe") => {
+                    let options: Vec<(String, String)> = get_shared_lists(guild_id, pool)
+                        .await?
+                        .into_iter()
+                        .map(|l| (l.name().clone(), l.name().clone()))
+                        .collect();
+                    if options.is_empty() {
+                        description = "There are no shared lists yet!".to_string();
+                        continue;
+                    }
+                    let mut components = select(&self.id, "list", "Choose a list", &options);
+                    components.add_action_row(button_row(&self.id, &[("back", "Back", ButtonStyle::Secondary)]));
+                    let choice = self
+                        .step("Shared Lists".to_string(), "Which list should this server use?".to_string(), components)
+                        .await?;
+                    let name = match choice.as_deref().and_then(|c| c.strip_prefix("list:")) {
+                        Some(name) => name.to_string(),
+                        None if choice.is_none() => return Ok(None),
+                        None => continue,
+                    };
+                    if let Some(list_id) = get_list_id(None, &name, pool).await? {
+                        insert_subscription(guild_id, list_id, pool, cache).await?;
+                        return Ok(Some(format!("subscribed to **{name}**")));
+                    }
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+    /// Waits for the admin who pressed upload to send a message in the wizard's channel.
+    async fn wait_for_upload(&self) -> Option<Arc<Message>> {
+        let mut collector = CollectReply::new(self.ctx)
+            .channel_id(self.message.channel_id)
+            .timeout(STEP_TIMEOUT);
+        if let Some(user_id) = self.user_id {
+            collector = collector.author_id(user_id);
+        }
+        collector.await
+    }
+    /// Lists which of [`ANNOUNCEMENT_PERMISSIONS`] the bot has in the announcement channel.
+    async fn check_permissions(&self, channel_id: Option<ChannelId>) -> String {
+        let channel = channel_id
+            .or(self.guild.system_channel_id)
+            .and_then(|id| self.guild.channels.get(&id)?.clone().guild());
+        let bot_id: UserId = self.ctx.cache.current_user_id();
+        let permissions = match (channel, self.guild.member(self.ctx, bot_id).await) {
+            (Some(channel), Ok(bot)) => self.guild.user_permissions_in(&channel, &bot).ok(),
+            _ => None,
+        };
+        permissions_summary(permissions)
+    }
+    async fn finish(&mut self, title: &str, description: String) -> Result<()> {
+        self.message
+            .edit(self.ctx, |m| {
+                m.embed(|e| e.title(title).description(description).color(Color::DARK_GOLD))
+                    .set_components(CreateComponents::default())
+            })
+            .await?;
+        Ok(())
+    }
+}
+fn button_row(id: &str, buttons: &[(&str, &str, ButtonStyle)]) -> crate::serenity::CreateActionRow {
+    let mut row = crate::serenity::CreateActionRow::default();
+    for (custom_id, label, style) in buttons {
+        row.create_button(|b| b.custom_id(format!("{id}{custom_id}")).label(label).style(*style));
+    }
+    row
+}
+fn buttons(id: &str, buttons: &[(&str, &str, ButtonStyle)]) -> CreateComponents {
+    let mut components = CreateComponents::default();
+    components.add_action_row(button_row(id, buttons));
+    components
+}
+/// A select menu of at most 25 `(label, value)` options, discord's limit.
+fn select(id: &str, custom_id: &str, placeholder: &str, options: &[(String, String)]) -> CreateComponents {
+    let mut components = CreateComponents::default();
+    if options.is_empty() {
+        return components;
+    }
+    components.create_action_row(|row| {
+        row.create_select_menu(|m| {
+            m.custom_id(format!("{id}{custom_id}"))
+                .placeholder(placeholder)
+                .options(|o| {
+                    for (label, value) in options.iter().take(25) {
+                        o.create_option(|o| o.label(label).value(value));
+                    }
+                    o
+                })
+        })
+    });
+    components
+}
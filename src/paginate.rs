@@ -1,5 +1,5 @@
 use crate::parsing::Items;
-use crate::requirements::RequirementList;
+use crate::requirements::{Requirement, RequirementList};
 use crate::serenity::{CollectComponentInteraction, InteractionResponseType};
 use crate::Context;
 use color_eyre::Result;
@@ -7,7 +7,15 @@ use getset::Getters;
 use poise::serenity_prelude::CreateActionRow;
 use poise::serenity_prelude::CreateButton;
 use serenity::utils::Color;
+use std::collections::BTreeMap;
 const MAX_PAGE_LENGTH: usize = 1365;
+/// Discord allows at most 25 options in a select menu.
+const MAX_MENU_OPTIONS: usize = 25;
+/// Categories per menu page when they don't fit in one menu, leaving room for the more option.
+const CATEGORIES_PER_MENU: usize = MAX_MENU_OPTIONS - 1;
+/// Select option labels are limited to 100 characters.
+const MAX_LABEL_LENGTH: usize = 100;
+const MORE_CATEGORIES: &str = "more";
 #[derive(Getters)]
 #[getset(get = "pub")]
 pub struct PaginateEmbed<'a> {
@@ -18,6 +26,9 @@ pub struct PaginateEmbed<'a> {
     footer: String,
     current_page: usize,
     empty_string: Option<&'a str>,
+    url: Option<String>,
+    categories: Vec<(String, usize)>,
+    category_menu_page: usize,
 }
 impl<'a> PaginateEmbed<'a> {
     fn get_current_page(&self) -> &str {
@@ -37,6 +48,9 @@ impl<'a> PaginateEmbed<'a> {
             pages,
             current_page: 0,
             empty_string: None,
+            url: None,
+            categories: Vec::new(),
+            category_menu_page: 0,
         }
     }
     fn next_clicked(&mut self) {
@@ -66,6 +80,64 @@ impl<'a> PaginateEmbed<'a> {
         self.empty_string = Some(string);
         self
     }
+    pub fn set_url(mut self, url: String) -> PaginateEmbed<'a> {
+        self.url = Some(url);
+        self
+    }
+    /// Category names with the page they start on, shown as a select menu when there is more than one.
+    pub fn set_categories(mut self, categories: Vec<(String, usize)>) -> PaginateEmbed<'a> {
+        self.categories = categories;
+        self
+    }
+    /// The label and value of each option in the category menu, values are indexes into the categories.
+    /// Past 25 categories the menu is paged with a last option that shows the next categories.
+    fn category_options(&self) -> Vec<(String, String)> {
+        let label = |i: usize, name: &str| {
+            if name.trim().is_empty() {
+                format!("Category {}", i + 1)
+            } else {
+                name.chars().take(MAX_LABEL_LENGTH).collect()
+            }
+        };
+        let options = self.categories.iter().enumerate();
+        if self.categories.len() <= MAX_MENU_OPTIONS {
+            return options
+                .map(|(i, (name, _))| (label(i, name), i.to_string()))
+                .collect();
+        }
+        let mut options: Vec<(String, String)> = options
+            .skip(self.category_menu_page * CATEGORIES_PER_MENU)
+            .take(CATEGORIES_PER_MENU)
+            .map(|(i, (name, _))| (label(i, name), i.to_string()))
+            .collect();
+        options.push(("More categories…".to_string(), MORE_CATEGORIES.to_string()));
+        options
+    }
+    fn category_selected(&mut self, value: &str) {
+        if value == MORE_CATEGORIES {
+            let menu_pages = self.categories.len().div_ceil(CATEGORIES_PER_MENU);
+            self.category_menu_page = (self.category_menu_page + 1) % menu_pages;
+            return;
+        }
+        if let Some((_, page)) = value.parse().ok().and_then(|i: usize| self.categories.get(i)) {
+            self.current_page = *page;
+        }
+        self.update_footer()
+    }
+    fn category_row(&self, menu_id: &str) -> CreateActionRow {
+        let mut category_row = CreateActionRow::default();
+        category_row.create_select_menu(|m| {
+            m.custom_id(menu_id)
+                .placeholder("Jump to category")
+                .options(|o| {
+                    for (label, value) in self.category_options() {
+                        o.create_option(|o| o.label(label).value(value));
+                    }
+                    o
+                })
+        });
+        category_row
+    }
     fn check_empty(&mut self) {
         if self.pages().is_empty() {
             let string = match self.empty_string() {
@@ -101,46 +173,55 @@ pub fn paginate_item(
     pages[*current_page].push_str(&item);
 }
 
-pub fn get_requirement_pages(req_list: RequirementList, items: Option<Items>) -> Vec<String> {
-    let mut pages: Vec<String> = vec![String::new()];
-    let mut current_page = 0;
-    let mut current_len = 0;
-    let items_present = items.is_some();
-    for challenge in req_list.requirements() {
-        let challenge_text = format!("__**{}**__\n", challenge.name());
-        check_new_page(
-            &mut pages,
-            &mut current_page,
-            &mut current_len,
-            challenge_text.len(),
-        );
-        pages[current_page].push_str(&challenge_text);
-        current_len += challenge_text.len();
+/// Lists each requirement with the items still missing, or every item when `items` is `None`.
+pub fn get_requirement_pages(
+    req_list: &RequirementList,
+    items: Option<Items>,
+) -> (Vec<String>, Vec<(String, usize)>) {
+    get_category_pages(req_list.requirements(), |challenge| {
+        let mut text = format!("__**{}**__\n", challenge.name());
         for item in challenge.required() {
-            if items_present
-        //something might be funky with this code here I had  debug statement on it
-                && items
-                    .as_ref()
-                    .is_some_and(|items| !items.items().contains_key(item))
-                || items.is_none()
+            if items
+                .as_ref()
+                .is_none_or(|items| !items.items().contains_key(item))
             {
-                let item_text = format!("{}\n", item);
-                check_new_page(
-                    &mut pages,
-                    &mut current_page,
-                    &mut current_len,
-                    item_text.len(),
-                );
-                pages[current_page].push_str(&item_text);
-                current_len += item_text.len();
+                text.push_str(item);
+                text.push('\n');
             };
         }
+        text
+    })
+}
+/// Groups requirements by [`Requirement::category`] with every category starting on a new page.
+/// Returns the pages along with the page each category starts on for [`PaginateEmbed::set_categories`].
+pub fn get_category_pages<'a>(
+    reqs: impl IntoIterator<Item = &'a Requirement>,
+    render: impl Fn(&Requirement) -> String,
+) -> (Vec<String>, Vec<(String, usize)>) {
+    let mut groups: BTreeMap<&str, Vec<&Requirement>> = BTreeMap::new();
+    for req in reqs {
+        groups.entry(req.category()).or_default().push(req);
     }
-    // Remove the last page if it is empty
-    if pages.last().is_some_and(|p| p.is_empty()) {
-        pages.pop();
+    let show_headers = groups.len() > 1;
+    let mut pages: Vec<String> = Vec::new();
+    let mut categories = Vec::new();
+    for (category, reqs) in groups {
+        categories.push((category.to_string(), pages.len()));
+        pages.push(String::new());
+        let mut current_page = pages.len() - 1;
+        let mut current_len = 0;
+        if show_headers {
+            let header = format!("**[{category}]**\n");
+            paginate_item(&mut pages, header, &mut current_len, &mut current_page);
+        }
+        for req in reqs {
+            // split by line so long requirements can continue on the next page
+            for line in render(req).split_inclusive('\n') {
+                paginate_item(&mut pages, line.to_string(), &mut current_len, &mut current_page);
+            }
+        }
     }
-    pages
+    (pages, categories)
 }
 pub async fn paginate(
     ctx: Context<'_>,
@@ -154,6 +235,7 @@ pub async fn paginate(
     left_button.label("◀").custom_id(&prev_button_id);
     let mut right_button = CreateButton::default();
     right_button.label("▶").custom_id(&next_button_id);
+    let category_menu_id = format!("{}category", ctx_id);
     let mut action_row = CreateActionRow::default();
     action_row
         .add_button(left_button)
@@ -162,15 +244,20 @@ pub async fn paginate(
     ctx.send(|f| {
         f.components(|f| {
             if paginate_struct.pages().len() > 1 {
-                f.add_action_row(action_row)
-            } else {
-                f
+                f.add_action_row(action_row.clone());
+            }
+            if paginate_struct.categories().len() > 1 {
+                f.add_action_row(paginate_struct.category_row(&category_menu_id));
             }
+            f
         })
         .embed(|f| {
             if paginate_struct.thumbnail().is_some() {
                 f.thumbnail(paginate_struct.thumbnail().as_ref().unwrap());
             }
+            if let Some(url) = paginate_struct.url() {
+                f.url(url);
+            }
             f.title(paginate_struct.title())
                 .description(paginate_struct.get_current_page())
                 .color(*paginate_struct.color())
@@ -193,6 +280,10 @@ pub async fn paginate(
             paginate_struct.next_clicked()
         } else if press.data.custom_id == prev_button_id {
             paginate_struct.previous_clicked()
+        } else if press.data.custom_id == category_menu_id {
+            if let Some(category) = press.data.values.first() {
+                paginate_struct.category_selected(category)
+            }
         } else {
             // This is an unrelated button interaction
             continue;
@@ -203,10 +294,22 @@ pub async fn paginate(
             .create_interaction_response(ctx, |b| {
                 b.kind(InteractionResponseType::UpdateMessage)
                     .interaction_response_data(|b| {
+                        // the category menu changes when it is paged
+                        if paginate_struct.categories().len() > MAX_MENU_OPTIONS {
+                            b.components(|f| {
+                                if paginate_struct.pages().len() > 1 {
+                                    f.add_action_row(action_row.clone());
+                                }
+                                f.add_action_row(paginate_struct.category_row(&category_menu_id))
+                            });
+                        }
                         b.embed(|b| {
                             if paginate_struct.thumbnail().is_some() {
                                 b.thumbnail(paginate_struct.thumbnail().unwrap());
                             }
+                            if let Some(url) = paginate_struct.url() {
+                                b.url(url);
+                            }
                             b.title(paginate_struct.title())
                                 .description(paginate_struct.get_current_page())
                                 .color(*paginate_struct.color())
@@ -235,4 +338,23 @@ mod tests {
             .for_each(|page| assert!(page.len() < MAX_PAGE_LENGTH));
         Ok(())
     }
+    #[test]
+    fn category_menu_test() {
+        let categories: Vec<(String, usize)> = (0..30).map(|i| (i.to_string(), i)).collect();
+        let mut embed = PaginateEmbed::new("test", None, Color::DARK_GOLD, vec![String::new(); 30])
+            .set_categories(categories);
+        embed.categories[3].0 = String::new();
+        let options = embed.category_options();
+        assert_eq!(options.len(), MAX_MENU_OPTIONS);
+        assert_eq!(options[3].0, "Category 4");
+        assert_eq!(options[24].1, MORE_CATEGORIES);
+        embed.category_selected(MORE_CATEGORIES);
+        let options = embed.category_options();
+        assert_eq!(options.len(), 7);
+        assert_eq!(options[0].1, "24");
+        embed.category_selected("29");
+        assert_eq!(embed.current_page, 29);
+        embed.category_selected(MORE_CATEGORIES);
+        assert_eq!(embed.category_options()[0].1, "0");
+    }
 }
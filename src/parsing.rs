@@ -10,12 +10,17 @@ use getset::Getters;
 use log::error;
 use num_format::{Locale, ToFormattedString};
 use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
 use scraper::{ElementRef, Html, Selector};
 use std::collections::HashMap;
 use std::future::Future;
 use std::iter::FromIterator;
 use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
 use tokio::fs;
+use tokio::time::{self, Duration, Interval, MissedTickBehavior};
 use std::fmt;
 pub struct ElementRefWrapper<'a>(pub ElementRef<'a>);
 impl fmt::Display for ElementRefWrapper<'_> {
@@ -74,7 +79,7 @@ pub struct FileFetcher<'a> {
     category: ParsingCategory,
 }
 impl<'a> FileFetcher<'a> {
-    pub fn new(file_path: &str) -> FileFetcher {
+    pub fn new(file_path: &str) -> FileFetcher<'_> {
         FileFetcher {
             file_path,
             category: ParsingCategory::CharacterPage,
@@ -133,6 +138,77 @@ impl CharacterFetcher {
         })
     }
 }
+/// How many character pages are fetched at once by [`fetch_characters_with_items`] and [`fetch_character_names`].
+const MAX_CONCURRENT_FETCHES: usize = 8;
+/// Least time between two charpage fetches started by [`fetch_character_names`],
+/// so a large import doesn't hammer the DF servers.
+const NAME_FETCH_DELAY: Duration = Duration::from_millis(500);
+/// Fetches the items of many characters concurrently.
+/// Characters that fail to fetch or no longer exist are logged and left out.
+pub async fn fetch_characters_with_items(df_ids: &[i32]) -> Vec<DFCharacterData> {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+    let mut fetches = JoinSet::new();
+    for &df_id in df_ids {
+        let permits = Arc::clone(&permits);
+        fetches.spawn(async move {
+            let _permit = permits.acquire_owned().await?;
+            CharacterFetcher::new(df_id, LookupCategory::Roles)
+                .category(ParsingCategory::Items)
+                .fetch_data()
+                .await?
+                .to_lookupstate()?
+                .extract_character_data()
+        });
+    }
+    let mut chars = Vec::with_capacity(df_ids.len());
+    while let Some(res) = fetches.join_next().await {
+        match res {
+            Ok(Ok(char)) => chars.push(char),
+            Ok(Err(why)) => error!("Failed fetching character: {why}"),
+            Err(why) => error!("Character fetch task failed: {why}"),
+        }
+    }
+    chars
+}
+/// Fetches the names of many characters concurrently, `None` for characters that don't exist.
+/// Characters that fail to fetch are logged and left out.
+pub async fn fetch_character_names(df_ids: &[i32]) -> HashMap<i32, Option<String>> {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+    let mut interval = time::interval(NAME_FETCH_DELAY);
+    // a burst of missed ticks would start several fetches at once after a slow batch
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let limiter: Arc<Mutex<Interval>> = Arc::new(Mutex::new(interval));
+    let mut fetches = JoinSet::new();
+    for &df_id in df_ids {
+        let permits = Arc::clone(&permits);
+        let limiter = Arc::clone(&limiter);
+        fetches.spawn(async move {
+            let _permit = permits.acquire_owned().await?;
+            limiter.lock().await.tick().await;
+            let name = match CharacterFetcher::new(df_id, LookupCategory::CharacterPage)
+                .fetch_data()
+                .await?
+                .to_lookupstate()?
+            {
+                LookupState::CharacterPage(char) => Some(char.name),
+                LookupState::FlashCharatcerPage(char) => char.get("Name").cloned(),
+                _ => None,
+            };
+            Ok::<_, color_eyre::Report>((df_id, name))
+        });
+    }
+    let mut names = HashMap::with_capacity(df_ids.len());
+    while let Some(res) = fetches.join_next().await {
+        match res {
+            Ok(Ok((df_id, name))) => {
+                names.insert(df_id, name);
+            }
+            Ok(Err(why)) => error!("Failed fetching character: {why}"),
+            Err(why) => error!("Character fetch task failed: {why}"),
+        }
+    }
+    names
+}
 #[derive(Getters)]
 #[getset(get = "pub")]
 pub struct CharacterData {
@@ -198,13 +274,34 @@ impl Dragon {
         Dragon { name, dragon_type }
     }
 }
-#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Deserialize, Serialize)]
 pub enum ItemTag {
     NDA,
     DA,
     DC,
     ARTIFACT,
 }
+impl fmt::Display for ItemTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ItemTag::NDA => write!(f, "NDA"),
+            ItemTag::DA => write!(f, "DA"),
+            ItemTag::DC => write!(f, "DC"),
+            ItemTag::ARTIFACT => write!(f, "ARTIFACT"),
+        }
+    }
+}
+impl ItemTag {
+    pub(crate) fn from_str(s: &str) -> Option<ItemTag> {
+        match s {
+            "NDA" => Some(ItemTag::NDA),
+            "DA" => Some(ItemTag::DA),
+            "DC" => Some(ItemTag::DC),
+            "ARTIFACT" => Some(ItemTag::ARTIFACT),
+            _ => None,
+        }
+    }
+}
 #[derive(Getters)]
 #[getset(get = "pub")]
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
@@ -0,0 +1,284 @@
+use crate::audit::{record_audit, AuditAction};
+use crate::embeds;
+use crate::lookup_df::{LookupCategory, LookupState};
+use crate::manage_users::insert_registration_in;
+use crate::parsing::CharacterFetcher;
+use crate::requests::CHARPAGE;
+use crate::serenity::{
+    ButtonStyle, ChannelId, Color, CreateEmbed, InteractionResponseType,
+    MessageComponentInteraction, UserId,
+};
+use crate::{Context, Data, Error};
+use color_eyre::Result;
+use getset::Getters;
+use sqlx::{query, query_as, PgConnection, PgPool};
+/// Prefix of the approval card's button ids, handled in [`crate::event_handler`].
+pub const REGISTRATION_BUTTON: &str = "registration";
+
+#[derive(poise::ChoiceParameter, PartialEq, Clone, Copy, Debug)]
+pub enum RegistrationStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+/// A member's request to have a character registered to them.
+#[derive(sqlx::FromRow, Getters, Debug)]
+#[getset(get = "pub")]
+pub struct RegistrationRequest {
+    request_id: i32,
+    guild_id: i64,
+    discord_id: i64,
+    discord_name: String,
+    df_id: i32,
+    character_name: String,
+    status: String,
+}
+/// Queues a request, `None` when the character already has one pending in the guild.
+pub async fn insert_request(
+    guild_id: i64,
+    discord_id: i64,
+    discord_name: &str,
+    df_id: i32,
+    character_name: &str,
+    pool: &PgPool,
+) -> Result<Option<RegistrationRequest>> {
+    let request = query_as!(
+        RegistrationRequest,
+        "INSERT INTO registration_requests (guild_id, discord_id, discord_name, df_id, character_name)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT DO NOTHING
+RETURNING request_id, guild_id, discord_id, discord_name, df_id, character_name, status",
+        guild_id,
+        discord_id,
+        discord_name,
+        df_id,
+        character_name
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(request)
+}
+/// Drops a request whose approval card couldn't be posted.
+pub async fn delete_request(request_id: i32, pool: &PgPool) -> Result<()> {
+    query!("DELETE FROM registration_requests WHERE request_id = $1", request_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+/// Moves a pending request to `status`, `None` if it was already reviewed.
+pub async fn review_request(
+    request_id: i32,
+    status: RegistrationStatus,
+    reviewed_by: &str,
+    pool: &PgPool,
+) -> Result<Option<RegistrationRequest>> {
+    let mut conn = pool.acquire().await?;
+    review_request_in(&mut conn, request_id, status, reviewed_by).await
+}
+/// [`review_request`] on a connection, so the review can share a transaction with the registration.
+pub async fn review_request_in(
+    conn: &mut PgConnection,
+    request_id: i32,
+    status: RegistrationStatus,
+    reviewed_by: &str,
+) -> Result<Option<RegistrationRequest>> {
+    let request = query_as!(
+        RegistrationRequest,
+        "UPDATE registration_requests SET status = $2, reviewed_by = $3, reviewed = now()
+WHERE request_id = $1 and status = 'Pending'
+RETURNING request_id, guild_id, discord_id, discord_name, df_id, character_name, status",
+        request_id,
+        status.to_string(),
+        reviewed_by
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(request)
+}
+/// Approves or denies a request from its approval card and updates the card.
+pub async fn handle_registration_button(
+    ctx: &crate::serenity::Context,
+    press: &MessageComponentInteraction,
+    data: &Data,
+) -> Result<()> {
+    let mut parts = press.data.custom_id.split(':').skip(1);
+    let (status, request_id) = match (parts.next(), parts.next().and_then(|id| id.parse().ok())) {
+        (Some("approve"), Some(id)) => (RegistrationStatus::Approved, id),
+        (Some("deny"), Some(id)) => (RegistrationStatus::Denied, id),
+        _ => return Ok(()),
+    };
+    let is_admin = press
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.administrator());
+    if !is_admin {
+        press
+            .create_interaction_response(ctx, |r| {
+                r.interaction_response_data(|d| {
+                    d.ephemeral(true)
+                        .content("Only admins can review registration requests")
+                })
+            })
+            .await?;
+        return Ok(());
+    }
+    let pool = data.db();
+    let reviewer = &press.user.name;
+    // the review and the registration it approves land together
+    let mut transaction = pool.begin().await?;
+    let request = match review_request_in(&mut transaction, request_id, status, reviewer).await? {
+        Some(request) => request,
+        None => {
+            press
+                .create_interaction_response(ctx, |r| {
+                    r.interaction_response_data(|d| {
+                        d.ephemeral(true).content("This request was already reviewed")
+                    })
+                })
+                .await?;
+            return Ok(());
+        }
+    };
+    let registered = match status {
+        RegistrationStatus::Approved => Some(
+            insert_registration_in(
+                &mut transaction,
+                request.discord_id,
+                &request.discord_name,
+                request.df_id,
+                &request.character_name,
+                reviewer,
+            )
+            .await?,
+        ),
+        _ => None,
+    };
+    transaction.commit().await?;
+    let (color, outcome) = match registered {
+        Some(true) => (Color::DARK_GREEN, format!("Approved by {reviewer}")),
+        Some(false) => (Color::DARK_RED, format!("Approved by {reviewer}, but the character was already registered")),
+        None => (Color::DARK_RED, format!("Denied by {reviewer}")),
+    };
+    record_audit(
+        pool,
+        Some(request.guild_id),
+        Some(press.user.id.0 as i64),
+        reviewer,
+        AuditAction::RegistrationReview,
+        Some(format!(
+            "{} ({}) requested by <@{}>",
+            request.character_name, request.df_id, request.discord_id
+        )),
+        Some(outcome.clone()),
+    )
+    .await?;
+    let mut card = press
+        .message
+        .embeds
+        .first()
+        .cloned()
+        .map(CreateEmbed::from)
+        .unwrap_or_default();
+    card.color(color).footer(|f| f.text(&outcome));
+    press
+        .create_interaction_response(ctx, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.set_embed(card).components(|c| c))
+        })
+        .await?;
+    // members may have DMs turned off
+    if let Ok(dm) = UserId(request.discord_id as u64).create_dm_channel(ctx).await {
+        let message = format!(
+            "Your registration of **{}** was {}",
+            request.character_name,
+            outcome.to_lowercase()
+        );
+        let _ = dm.say(ctx, message).await;
+    }
+    Ok(())
+}
+/// Ask this server's admins to register a DF character to you
+#[poise::command(slash_command, guild_only)]
+pub async fn request_registration(
+    ctx: Context<'_>,
+    #[description = "DF ID of your character"] df_id: i32,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let pool = ctx.data().db();
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let config = ctx.data().guild_config().get(guild_id, pool).await?;
+    let channel_id = match config.log_channel_id() {
+        Some(channel_id) => ChannelId(*channel_id as u64),
+        None => {
+            ctx.say("This server has no admin channel for requests, ask an admin to set one with `/settings log_channel`")
+                .await?;
+            return Ok(());
+        }
+    };
+    if let Some(owner) = query!("select discord_id from df_characters where df_id = $1", df_id)
+        .fetch_optional(pool)
+        .await?
+    {
+        ctx.say(format!("DF ID {df_id} is already registered to <@{}>", owner.discord_id))
+            .await?;
+        return Ok(());
+    }
+    let lookupstate = CharacterFetcher::new(df_id, LookupCategory::CharacterPage)
+        .fetch_data()
+        .await?
+        .to_lookupstate()?;
+    let (name, details) = match lookupstate {
+        LookupState::CharacterPage(char) => {
+            let da = if char.dragon_amulet { "Yes" } else { "No" };
+            let details = format!("**Level:** {}\n**DA:** {da}", char.level);
+            (char.name, details)
+        }
+        LookupState::FlashCharatcerPage(char) => {
+            let get = |key: &str| char.get(key).cloned().unwrap_or_default();
+            (get("Name"), format!("**Level:** {}\n**DA:** {}", get("Level"), get("DA")))
+        }
+        _ => return Ok(embeds::not_found_embed(ctx, df_id).await?),
+    };
+    let author = ctx.author();
+    let request = match insert_request(guild_id, author.id.0 as i64, &author.name, df_id, &name, pool).await? {
+        Some(request) => request,
+        None => {
+            ctx.say(format!("**{name}** is already waiting for approval")).await?;
+            return Ok(());
+        }
+    };
+    let id = request.request_id;
+    let card = channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| {
+                e.title(format!("Registration Request: {name}"))
+                    .url(format!("{CHARPAGE}{df_id}"))
+                    .color(Color::DARK_GOLD)
+                    .author(|a| a.name(&author.name).icon_url(author.face()))
+                    .description(format!("<@{}> requests **DF ID:** {df_id}\n{details}", author.id.0))
+            })
+            .components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id(format!("{REGISTRATION_BUTTON}:approve:{id}"))
+                            .label("Approve")
+                            .style(ButtonStyle::Success)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(format!("{REGISTRATION_BUTTON}:deny:{id}"))
+                            .label("Deny")
+                            .style(ButtonStyle::Danger)
+                    })
+                })
+            })
+        })
+        .await;
+    // without a card nobody could ever review the request
+    if let Err(why) = card {
+        delete_request(id, pool).await?;
+        return Err(why.into());
+    }
+    ctx.say(format!("Sent **{name}** to the admins for approval")).await?;
+    Ok(())
+}
@@ -14,6 +14,8 @@ pub const ASCEND_DA_IMGUR: &str = "https://i.imgur.com/MHJCKwE.png";
 pub const COLOR_SITE: &str = "https://www.color-hex.com/color/";
 pub const DESIGN_NOTES_LINK: &str = "https://www.dragonfable.com/gamedesignnotes/date";
 pub const DF_LINK: &str = "https://www.dragonfable.com/";
+pub const AQW_DESIGN_NOTES_LINK: &str = "https://www.aq.com/gamedesignnotes/date";
+pub const AQW_LINK: &str = "https://www.aq.com/";
 pub fn open_file(file_path:&str)->Result<String>{
         Ok(read_to_string(file_path)?)
 }
@@ -0,0 +1,296 @@
+use crate::audit::{audit_change, audit_global_change, AuditAction};
+use crate::dev_tools::is_superuser_check;
+use crate::embeds;
+use crate::guild_settings::set_shared_requirements;
+use crate::requirements::RequirementCache;
+use crate::rng::random_rgb;
+use crate::{Context, Error};
+use color_eyre::Result;
+use getset::Getters;
+use poise::serenity_prelude::Attachment;
+use sqlx::{query, query_as, PgConnection, PgPool};
+use std::fmt::Write;
+/// Name of a guild's own requirement list.
+pub const ROLES_LIST: &str = "Roles";
+/// Name of the list holding a guild's own Inn items.
+pub const INN_COLLECTION: &str = "Inn";
+/// Shared list evaluated by [`crate::lookup_df::LookupCategory::Ascendancies`].
+pub const ASCENDANCIES_LIST: &str = "Ascendancies";
+/// Shared Inn list used by guilds that have not defined their own.
+pub const INN_LIST: &str = "Inn Items";
+
+/// A shared requirement list any guild can subscribe to.
+#[derive(sqlx::FromRow, Getters, Debug)]
+#[getset(get = "pub")]
+pub struct SharedList {
+    list_id: i32,
+    name: String,
+    owner_id: Option<i64>,
+    description: Option<String>,
+    requirement_count: Option<i64>,
+    subscribed: Option<bool>,
+}
+/// Gets the id of a guild's list, or of a shared list when `guild_id` is `None`.
+pub async fn get_list_id(guild_id: Option<i64>, name: &str, pool: &PgPool) -> Result<Option<i32>> {
+    let record = query!(
+        "select list_id from requirement_lists where guild_id is not distinct from $1 and name = $2",
+        guild_id,
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(record.map(|r| r.list_id))
+}
+/// How many requirements a guild's list, or a shared list when `guild_id` is `None`, has.
+pub async fn requirement_count(guild_id: Option<i64>, name: &str, pool: &PgPool) -> Result<i64> {
+    let record = query!(
+        r#"select count(r.requirementid) as "count!" from requirement_lists l
+join requirements r on r.list_id = l.list_id
+where l.guild_id is not distinct from $1 and l.name = $2"#,
+        guild_id,
+        name
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(record.count)
+}
+/// Creates the list if it does not exist yet and returns its id.
+/// `owner_id` and `description` only overwrite the stored values when given.
+pub async fn upsert_list(
+    conn: &mut PgConnection,
+    guild_id: Option<i64>,
+    name: &str,
+    owner_id: Option<i64>,
+    description: Option<&str>,
+) -> Result<i32> {
+    // shared and guild lists are unique on separate partial indexes
+    let list_id = match guild_id {
+        Some(guild_id) => {
+            query!(
+                "INSERT INTO requirement_lists (guild_id, name, owner_id, description)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (guild_id, name) WHERE guild_id IS NOT NULL
+DO UPDATE SET
+    owner_id = COALESCE(EXCLUDED.owner_id, requirement_lists.owner_id),
+    description = COALESCE(EXCLUDED.description, requirement_lists.description)
+RETURNING list_id",
+                guild_id,
+                name,
+                owner_id,
+                description
+            )
+            .fetch_one(conn)
+            .await?
+            .list_id
+        }
+        None => {
+            query!(
+                "INSERT INTO requirement_lists (name, owner_id, description)
+VALUES ($1, $2, $3)
+ON CONFLICT (name) WHERE guild_id IS NULL
+DO UPDATE SET
+    owner_id = COALESCE(EXCLUDED.owner_id, requirement_lists.owner_id),
+    description = COALESCE(EXCLUDED.description, requirement_lists.description)
+RETURNING list_id",
+                name,
+                owner_id,
+                description
+            )
+            .fetch_one(conn)
+            .await?
+            .list_id
+        }
+    };
+    Ok(list_id)
+}
+/// Subscribes a guild that already has a `guild_settings` row to a shared list.
+pub async fn insert_subscription(
+    guild_id: i64,
+    list_id: i32,
+    pool: &PgPool,
+    cache: &RequirementCache,
+) -> Result<()> {
+    query!(
+        "INSERT INTO list_subscriptions (guild_id, list_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        guild_id,
+        list_id
+    )
+    .execute(pool)
+    .await?;
+    cache.invalidate_guild(guild_id).await;
+    Ok(())
+}
+/// All shared lists, marking the ones `guild_id` is subscribed to.
+pub async fn get_shared_lists(guild_id: i64, pool: &PgPool) -> Result<Vec<SharedList>> {
+    let lists = query_as!(
+        SharedList,
+        "select l.list_id, l.name, l.owner_id, l.description,
+    (select count(*) from requirements r where r.list_id = l.list_id) as requirement_count,
+    exists(select 1 from list_subscriptions s where s.list_id = l.list_id and s.guild_id = $1) as subscribed
+from requirement_lists l where l.guild_id is null order by l.name",
+        guild_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(lists)
+}
+pub async fn autocomplete_shared_list(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<poise::AutocompleteChoice<String>> {
+    let lists = query!(
+        "select name from requirement_lists where guild_id is null and name ilike '%' || $1 || '%' order by name limit 25",
+        partial
+    )
+    .fetch_all(&ctx.data().db_connection)
+    .await
+    .unwrap_or_default();
+    lists
+        .into_iter()
+        .map(|l| poise::AutocompleteChoice {
+            name: l.name.clone(),
+            value: l.name,
+        })
+        .collect()
+}
+/// Show the shared requirement lists this guild can subscribe to
+#[poise::command(slash_command, guild_only)]
+pub async fn shared_lists(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let lists = get_shared_lists(guild_id, ctx.data().db()).await?;
+    let description = lists.iter().fold(String::new(), |mut acc, l| {
+        let _ = write!(
+            acc,
+            "**{}**{}\n*{} requirements*",
+            l.name(),
+            if l.subscribed().unwrap_or(false) { " ✅" } else { "" },
+            l.requirement_count().unwrap_or(0)
+        );
+        if let Some(owner_id) = l.owner_id() {
+            let _ = write!(acc, " *owned by <@{owner_id}>*");
+        }
+        let _ = writeln!(acc, "\n{}", l.description().as_deref().unwrap_or_default());
+        acc
+    });
+    ctx.send(|f| {
+        f.embed(|f| {
+            f.title("Shared Requirement Lists")
+                .color(random_rgb())
+                .description(if lists.is_empty() {
+                    "There are no shared lists yet!".to_string()
+                } else {
+                    description
+                })
+        })
+    })
+    .await?;
+    Ok(())
+}
+/// Include a shared requirement list in this guild's roles
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn subscribe_list(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_shared_list"]
+    #[description = "shared list to subscribe to"]
+    list: String,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let guild_name = ctx.guild().unwrap().name;
+    let list_id = match get_list_id(None, &list, pool).await? {
+        Some(list_id) => list_id,
+        None => return Ok(embeds::no_list_embed(ctx, &list).await?),
+    };
+    query!(
+        "INSERT INTO guild_settings (guild_id, guild_name)
+VALUES ($1, $2)
+ON CONFLICT (guild_id)
+DO NOTHING",
+        guild_id,
+        guild_name
+    )
+    .execute(pool)
+    .await?;
+    insert_subscription(guild_id, list_id, pool, ctx.data().requirement_cache()).await?;
+    audit_change(ctx, AuditAction::ListSubscription, None, Some(format!("Subscribed to {list}"))).await?;
+    ctx.reply(format!("{guild_name} is now subscribed to **{list}**"))
+        .await?;
+    Ok(())
+}
+/// Stop including a shared requirement list in this guild's roles
+#[poise::command(
+    slash_command,
+    required_permissions = "ADMINISTRATOR",
+    default_member_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn unsubscribe_list(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_shared_list"]
+    #[description = "shared list to unsubscribe from"]
+    list: String,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let guild_id = ctx.guild_id().unwrap().0 as i64;
+    let res = query!(
+        "DELETE FROM list_subscriptions WHERE guild_id = $1 and list_id = (select list_id from requirement_lists where guild_id is null and name = $2)",
+        guild_id,
+        list
+    )
+    .execute(pool)
+    .await?;
+    ctx.data().requirement_cache().invalidate_guild(guild_id).await;
+    if res.rows_affected() == 0 {
+        ctx.reply(format!("This guild is not subscribed to **{list}**"))
+            .await?;
+    } else {
+        audit_change(ctx, AuditAction::ListSubscription, Some(format!("Subscribed to {list}")), None).await?;
+        ctx.reply(format!("Unsubscribed from **{list}**")).await?;
+    }
+    Ok(())
+}
+/// Create or replace a shared requirement list
+#[poise::command(slash_command, check = "is_superuser_check")]
+pub async fn set_shared_list(
+    ctx: Context<'_>,
+    #[description = "name of the shared list"] name: String,
+    file: Attachment,
+    #[description = "what the list is for"] description: Option<String>,
+) -> Result<(), Error> {
+    set_shared_requirements(ctx, file, &name, description.as_deref()).await?;
+    Ok(())
+}
+/// Delete a shared requirement list you own
+#[poise::command(slash_command)]
+pub async fn delete_shared_list(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_shared_list"]
+    #[description = "shared list to delete"]
+    list: String,
+) -> Result<(), Error> {
+    let pool = ctx.data().db();
+    let owner_id = ctx.author().id.0 as i64;
+    let is_superuser = is_superuser_check(ctx).await?;
+    let res = query!(
+        "DELETE FROM requirement_lists WHERE guild_id is null and name = $1 and (owner_id = $2 or $3)",
+        list,
+        owner_id,
+        is_superuser
+    )
+    .execute(pool)
+    .await?;
+    ctx.data().requirement_cache().invalidate_all().await;
+    if res.rows_affected() == 0 {
+        ctx.reply(format!("**{list}** does not exist or you do not own it"))
+            .await?;
+    } else {
+        audit_global_change(ctx, AuditAction::ListDelete, Some(format!("Shared list {list}")), None).await?;
+        ctx.reply(format!("Deleted shared list **{list}**")).await?;
+    }
+    Ok(())
+}
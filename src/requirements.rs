@@ -1,28 +1,41 @@
-use crate::parsing::{DFCharacterData, Items, WarList};
+use crate::parsing::{DFCharacterData, ItemTag, Items, WarList};
+use chrono::{NaiveDate, Utc};
 use color_eyre::Result;
 use serde_derive::{Deserialize, Serialize};
 use sqlx::{query, PgPool};
 use std::cmp::Ordering;
 use std::fmt;
+use std::fmt::Write;
 use std::fs::File;
+use std::collections::HashMap;
+use std::future::Future;
 use std::io::BufReader;
+use std::sync::Arc;
 use crate::{Context, Error};
 use crate::lookup_df::LookupCategory;
+use crate::db::query_with_id;
 use crate::manage_users::autocomplete_character;
 use crate::paginate::{get_requirement_pages, paginate, PaginateEmbed};
-use crate::parsing::{CharacterFetcher, ParsingCategory};
-use crate::db::INN_GUILD_ID;
+use crate::embeds::guild_only_with_id;
+use crate::paginate::paginate_item;
+use crate::parsing::{fetch_characters_with_items, CharacterFetcher, ParsingCategory};
+use crate::requirement_lists::{
+    get_list_id, INN_COLLECTION, INN_LIST, ROLES_LIST,
+};
 use crate::rng::random_rgb;
-use crate::serenity::{Color, User};
+use num_format::{Locale, ToFormattedString};
+use crate::serenity::{Color, GuildId, RwLock, User};
+use poise::futures_util::StreamExt;
 
 pub enum RequirementListType {
     Roles,
     Ascend,
 }
+pub const UNCATEGORIZED: &str = "Other";
 fn req_type_item() -> ReqType {
     ReqType::Item
 }
-#[derive(Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Requirement {
     pub name: String,
     pub description: Option<String>,
@@ -32,6 +45,36 @@ pub struct Requirement {
     #[serde(default = "req_type_item")]
     pub req_type: ReqType,
     pub amount: Option<i32>,
+    /// First day the requirement can be earned.
+    #[serde(default)]
+    pub start: Option<NaiveDate>,
+    /// Last day the requirement can be earned.
+    #[serde(default)]
+    pub end: Option<NaiveDate>,
+    /// Only characters last played within the window qualify.
+    #[serde(default)]
+    pub require_active: bool,
+    /// Worth of the requirement towards a character's score.
+    pub points: Option<i32>,
+    /// Group the requirement is listed under, e.g. "Wars" or "Classes".
+    pub category: Option<String>,
+    /// Amounts to generate one ladder rung each for, only used in JSON.
+    /// A `{}` in the name or description is replaced with the amount, otherwise the rung number is appended to the name.
+    #[serde(default)]
+    pub thresholds: Option<Vec<i32>>,
+    /// Name of the ladder this requirement is a rung of.
+    pub ladder: Option<String>,
+    /// Items the character must not own.
+    pub excluded: Option<Vec<String>>,
+    /// Item classes the character must not own any of, e.g. `DA` for an NDA-only challenge.
+    pub excluded_tags: Option<Vec<ItemTag>>,
+}
+/// Where today falls relative to a requirement's start/end dates.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RequirementWindow {
+    Upcoming(NaiveDate),
+    Open,
+    Expired(NaiveDate),
 }
 impl Requirement {
     pub fn name(&self) -> &String {
@@ -56,6 +99,47 @@ impl Requirement {
         self.amount
             .unwrap_or_else(|| panic!("Role: {} Expected 'amount'", self.name()))
     }
+    /// Requirements without a category are listed under [`UNCATEGORIZED`].
+    pub fn category(&self) -> &str {
+        self.category.as_deref().unwrap_or(UNCATEGORIZED)
+    }
+    /// What the character owns that this requirement excludes, empty when nothing is.
+    pub fn exclusion_violations(&self, items: &Items) -> Vec<String> {
+        let mut violations: Vec<String> = self
+            .excluded
+            .iter()
+            .flatten()
+            .filter(|&item| items.contains(item))
+            .cloned()
+            .collect();
+        for tag in self.excluded_tags.iter().flatten() {
+            let count = items.items().values().filter(|item| item.tag == *tag).count();
+            if count > 0 {
+                violations.push(format!("{count} {tag} items"));
+            }
+        }
+        violations
+    }
+    /// Requirements without points are worth 1.
+    pub fn points(&self) -> i32 {
+        self.points.unwrap_or(1)
+    }
+    pub fn window(&self, today: NaiveDate) -> RequirementWindow {
+        match (self.start, self.end) {
+            (Some(start), _) if today < start => RequirementWindow::Upcoming(start),
+            (_, Some(end)) if today > end => RequirementWindow::Expired(end),
+            _ => RequirementWindow::Open,
+        }
+    }
+    /// Whether the character can earn this requirement today.
+    fn earnable(&self, char: &DFCharacterData, today: NaiveDate) -> bool {
+        if self.window(today) != RequirementWindow::Open {
+            return false;
+        }
+        !self.require_active
+            || (self.start.is_none_or(|start| char.last_played >= start)
+                && self.end.is_none_or(|end| char.last_played <= end))
+    }
 }
 fn max_last(a: &Requirement, b: &Requirement) -> Ordering {
     match (&a.req_type, &b.req_type) {
@@ -66,7 +150,18 @@ fn max_last(a: &Requirement, b: &Requirement) -> Ordering {
     }
 }
 
-#[derive(Deserialize, Debug, Serialize)]
+/// A character's place on a ladder, see [`ladder_progress`].
+#[derive(Debug)]
+pub struct LadderProgress<'a> {
+    pub ladder: &'a str,
+    /// Highest rung the character has earned.
+    pub earned: Option<&'a Requirement>,
+    /// Rung after [`LadderProgress::earned`].
+    pub next: Option<&'a Requirement>,
+    /// How far the character is towards [`LadderProgress::next`] when the requirement type is counted.
+    pub current: Option<i32>,
+}
+#[derive(Clone, Deserialize, Debug, Serialize)]
 pub struct RequirementList(Vec<Requirement>);
 impl RequirementList {
     pub fn requirements(&self) -> &[Requirement] {
@@ -75,11 +170,54 @@ impl RequirementList {
     fn sort(&mut self) {
         self.0.sort_by(max_last)
     }
+    /// Replaces every requirement with `thresholds` by one rung per threshold.
+    /// Each rung has the previous one as a prereq so only the highest earned rung is shown.
+    fn expand_ladders(&mut self) {
+        let reqs = std::mem::take(&mut self.0);
+        for req in reqs {
+            let mut thresholds = match &req.thresholds {
+                Some(thresholds) => thresholds.clone(),
+                None => {
+                    self.0.push(req);
+                    continue;
+                }
+            };
+            thresholds.sort_unstable();
+            let ladder = req.name.replace("{}", "").trim().to_string();
+            let mut prev: Option<String> = None;
+            for (i, amount) in thresholds.into_iter().enumerate() {
+                let formatted = amount.to_formatted_string(&Locale::en);
+                let name = if req.name.contains("{}") {
+                    req.name.replace("{}", &formatted)
+                } else {
+                    format!("{} {}", req.name, i + 1)
+                };
+                let prereqs = match &prev {
+                    Some(prev) => Some(vec![prev.clone()]),
+                    None => req.prereqs.clone(),
+                };
+                self.0.push(Requirement {
+                    name: name.clone(),
+                    description: req.description.as_ref().map(|d| d.replace("{}", &formatted)),
+                    prereqs,
+                    amount: Some(amount),
+                    thresholds: None,
+                    ladder: Some(ladder.clone()),
+                    ..req.clone()
+                });
+                prev = Some(name);
+            }
+        }
+    }
     pub fn sort_alphabetical(&mut self) {
         self.0.sort_by(|a, b| a.name().cmp(b.name()))
     }
+    /// Total points of every requirement in the list.
+    pub fn score(&self) -> i32 {
+        self.0.iter().map(Requirement::points).sum()
+    }
 }
-#[derive(Serialize, Debug, Deserialize, Eq, PartialEq, Hash)]
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq, Hash)]
 #[allow(non_snake_case)]
 #[serde(rename_all = "PascalCase")]
 pub enum ReqType {
@@ -100,6 +238,8 @@ pub enum ReqType {
     #[serde(rename = "Item/Stackable")]
     ItemStackable,
     Inn,
+    /// Only checks `excluded` and `excluded_tags`.
+    Exclusion,
 }
 
 impl fmt::Display for ReqType {
@@ -116,6 +256,7 @@ impl fmt::Display for ReqType {
             ReqType::ItemStackable => write!(f, "Item/Stackable"),
             ReqType::Inn => write!(f, "Inn"),
             ReqType::ItemUnique => write!(f, "Item/Unique"),
+            ReqType::Exclusion => write!(f, "Exclusion"),
         }
     }
 }
@@ -134,6 +275,7 @@ impl ReqType {
             "Gold" => Some(ReqType::Gold),
             "Inn" => Some(ReqType::Inn),
             "Item/Unique" => Some(ReqType::ItemUnique),
+            "Exclusion" => Some(ReqType::Exclusion),
             _ => None, // Return None if the string does not match any variant
         }
     }
@@ -142,70 +284,94 @@ pub fn get_requirements_file(path: &str) -> Result<RequirementList> {
     let file = File::open(format!("JSONS/{path}"))?;
     let reader = BufReader::new(file);
     let mut roles: RequirementList = serde_json::from_reader(reader)?;
+    roles.expand_ladders();
     roles.sort();
     Ok(roles)
 }
 
+/// Gets a guild's own requirements along with every shared list it subscribes to.
 pub async fn get_requirements(guild_id: i64, pool: &PgPool) -> Result<RequirementList> {
+    let list_ids = query!(
+        "select list_id from requirement_lists where guild_id = $1 and name = $2
+union select list_id from list_subscriptions where guild_id = $1",
+        guild_id,
+        ROLES_LIST
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|r| r.list_id)
+    .collect::<Vec<i32>>();
+    get_requirements_from_lists(&list_ids, pool).await
+}
+/// Gets a named list (e.g. [`INN_COLLECTION`]) belonging to a guild.
+pub async fn get_collection(guild_id: i64, collection: &str, pool: &PgPool) -> Result<RequirementList> {
+    let list_id = get_list_id(Some(guild_id), collection, pool).await?;
+    get_requirements_from_lists(&Vec::from_iter(list_id), pool).await
+}
+/// Gets a shared list by name (e.g. [`crate::requirement_lists::ASCENDANCIES_LIST`]).
+pub async fn get_shared_list(name: &str, pool: &PgPool) -> Result<RequirementList> {
+    let list_id = get_list_id(None, name, pool).await?;
+    get_requirements_from_lists(&Vec::from_iter(list_id), pool).await
+}
+/// Gets the Inn list used to evaluate [`ReqType::Inn`].
+/// Uses the guild's own Inn list when it has one and the shared [`INN_LIST`] otherwise.
+pub async fn get_inn_list(guild_id: Option<i64>, pool: &PgPool) -> Result<RequirementList> {
+    if let Some(guild_id) = guild_id {
+        let inn_list = get_collection(guild_id, INN_COLLECTION, pool).await?;
+        if !inn_list.requirements().is_empty() {
+            return Ok(inn_list);
+        }
+    }
+    get_shared_list(INN_LIST, pool).await
+}
+/// Loads every requirement in the given lists with their items and prereqs in a single query.
+async fn get_requirements_from_lists(list_ids: &[i32], pool: &PgPool) -> Result<RequirementList> {
     let reqs = query!(
-        "select * from requirements where guild_id = $1 order by name",
-        guild_id
+        r#"select r.name, r.description, r.type, r.amount, r.start_date, r.end_date, r.require_active, r.points, r.category, r.ladder,
+    r.excluded_items as "excluded!", r.excluded_tags as "excluded_tags!",
+    array(select i.itemname from requireditems i where i.requirementid = r.requirementid order by i.requireditemid) as "items!",
+    array(select p.name from prerequisites pr join requirements p on p.requirementid = pr.prerequisiterequirementid
+        where pr.requirementid = r.requirementid order by pr.prerequisiteid) as "prereqs!"
+from requirements r where r.list_id = any($1) order by r.name"#,
+        list_ids
     )
     .fetch_all(pool)
     .await?;
-    let mut requirements: RequirementList = RequirementList(vec![]);
-    for req in reqs {
-        let name = req.name;
-        let description = req.description;
-        let req_id = req.requirementid;
-        let req_type = ReqType::from_str(&req.r#type).unwrap();
-        let amount = req.amount;
-        let prereq_records = query!(
-            "select prerequisiterequirementid from prerequisites where RequirementId = $1",
-            req.requirementid
-        )
-        .fetch_all(pool)
-        .await?;
-        let items = query!(
-            "select itemname from requireditems where requirementid = $1",
-            req_id
-        )
-        .fetch_all(pool)
-        .await?
-        .iter()
-        .map(|r| r.itemname.clone())
-        .collect::<Vec<String>>();
-        let prereqs = if prereq_records.is_empty() {
-            None
-        } else {
-            let mut prereqs = vec![];
-            for prereq_id in prereq_records {
-                let name = query!(
-                    "select name from requirements where requirementid = $1",
-                    prereq_id.prerequisiterequirementid
+    let mut requirements: RequirementList = RequirementList(
+        reqs.into_iter()
+            .map(|req| Requirement {
+                name: req.name,
+                description: req.description,
+                required: Some(req.items).filter(|items| !items.is_empty()),
+                req_type: ReqType::from_str(&req.r#type).unwrap(),
+                prereqs: Some(req.prereqs).filter(|prereqs| !prereqs.is_empty()),
+                amount: req.amount,
+                start: req.start_date,
+                end: req.end_date,
+                require_active: req.require_active,
+                points: req.points,
+                category: req.category,
+                thresholds: None,
+                ladder: req.ladder,
+                excluded: Some(req.excluded).filter(|excluded| !excluded.is_empty()),
+                excluded_tags: Some(
+                    req.excluded_tags
+                        .iter()
+                        .filter_map(|tag| ItemTag::from_str(tag))
+                        .collect::<Vec<ItemTag>>(),
                 )
-                .fetch_one(pool)
-                .await?;
-                prereqs.push(name.name);
-            }
-            Some(prereqs)
-        };
-        let required = if items.is_empty() { None } else { Some(items) };
-        requirements.0.push(Requirement {
-            name,
-            description,
-            required,
-            req_type,
-            prereqs,
-            amount,
-        })
-    }
+                .filter(|tags| !tags.is_empty()),
+            })
+            .collect(),
+    );
     requirements.sort();
     Ok(requirements)
 }
 
 pub fn get_requirements_bytes(bytes: &[u8]) -> Result<RequirementList> {
     let mut roles: RequirementList = serde_json::from_slice(bytes)?;
+    roles.expand_ladders();
     roles.sort();
     Ok(roles)
 }
@@ -278,18 +444,38 @@ fn check_item_stackable(role: &Requirement, items: &Items) -> bool {
             .any(|(name, item)| name == req_item && item.amount() >= req_amount)
     })
 }
-fn check_all_inn_reqs(items: &Items) -> bool {
-    let list = get_requirements_file("InnList.json").expect("failed to get in list");
-    list.requirements()
-        .iter()
-        .all(|innreq| innreq.required().iter().all(|i| items.contains(i)))
+fn check_all_inn_reqs(inn_list: Option<&RequirementList>, items: &Items) -> bool {
+    inn_list.is_some_and(|list| {
+        !list.requirements().is_empty()
+            && list
+                .requirements()
+                .iter()
+                .all(|innreq| innreq.required().iter().all(|i| items.contains(i)))
+    })
+}
+fn aquired_roles_indexes(
+    roles: &RequirementList,
+    char: &DFCharacterData,
+    inn_list: Option<&RequirementList>,
+) -> Vec<usize> {
+    evaluate_indexes(roles, char, inn_list).0
 }
-fn aquired_roles_indexes(roles: &mut RequirementList, char: &DFCharacterData) -> Vec<usize> {
+/// Returns the indexes of the acquired roles and of the roles only missed because of their exclusions.
+fn evaluate_indexes(
+    roles: &RequirementList,
+    char: &DFCharacterData,
+    inn_list: Option<&RequirementList>,
+) -> (Vec<usize>, Vec<(usize, Vec<String>)>) {
     let char_items = char.item_list.as_ref().expect("expected char items");
     let dups = char_items.dups();
     let roles_list = roles.requirements();
     let mut roles_indexes_to_remove: Vec<usize> = vec![];
+    let mut excluded = vec![];
+    let today = Utc::now().date_naive();
     for (i, role) in roles_list.iter().enumerate() {
+        if !role.earnable(char, today) {
+            continue;
+        }
         let aquired = match role.req_type {
             ReqType::Wars => check_war(role, char),
             ReqType::Item => check_item(role, char_items),
@@ -303,16 +489,35 @@ fn aquired_roles_indexes(roles: &mut RequirementList, char: &DFCharacterData) ->
             ReqType::ItemStackable => check_item_stackable(role, char_items),
             ReqType::Inn => {
                 check_max_role(roles_list, role, &roles_indexes_to_remove)
-                    && check_all_inn_reqs(char_items)
+                    && check_all_inn_reqs(inn_list, char_items)
             }
+            ReqType::Exclusion => true,
         };
-        if aquired {
+        if !aquired {
+            continue;
+        }
+        let violations = role.exclusion_violations(char_items);
+        if violations.is_empty() {
             roles_indexes_to_remove.push(i);
+        } else {
+            excluded.push((i, violations));
         }
     }
-    roles_indexes_to_remove
+    (roles_indexes_to_remove, excluded)
+}
+/// Requirements the character meets except for owning something they exclude, with what they own.
+pub fn excluded_requirements<'a>(
+    roles: &'a RequirementList,
+    char: &DFCharacterData,
+    inn_list: Option<&RequirementList>,
+) -> Vec<(&'a Requirement, Vec<String>)> {
+    evaluate_indexes(roles, char, inn_list)
+        .1
+        .into_iter()
+        .map(|(i, violations)| (&roles.0[i], violations))
+        .collect()
 }
-fn prereq_roles_to_remove(roles: &[Requirement]) -> Vec<usize> {
+fn prereq_roles_to_remove(roles: &[&Requirement]) -> Vec<usize> {
     let mut prereq_roles = Vec::new();
     for role in roles {
         if let Some(prereqs) = &role.prereqs {
@@ -327,27 +532,255 @@ fn prereq_roles_to_remove(roles: &[Requirement]) -> Vec<usize> {
     }
     prereq_roles
 }
+/// Checks a character against a guild's requirements and the shared lists it subscribes to.
 pub async fn check_requirements(
     char: &DFCharacterData,
     guild_id: i64,
     pool: &PgPool,
+    cache: &RequirementCache,
+) -> Result<RequirementList> {
+    let roles = cache.requirements(guild_id, pool).await?;
+    let inn_list = load_inn_list(&roles, Some(guild_id), pool, cache).await?;
+    Ok(evaluate_requirements(&roles, char, inn_list.as_deref()))
+}
+/// Checks a character against a single shared list (e.g. [`crate::requirement_lists::ASCENDANCIES_LIST`]).
+pub async fn check_shared_requirements(
+    char: &DFCharacterData,
+    name: &str,
+    pool: &PgPool,
+    cache: &RequirementCache,
 ) -> Result<RequirementList> {
-    let mut roles = get_requirements(guild_id, pool).await?;
-    let mut aquired_roles = aquired_roles_indexes(&mut roles, char);
-    aquired_roles.sort_by(|a, b| b.cmp(a));
-    let mut roles: Vec<Requirement> = aquired_roles
+    let roles = cache.shared_list(name, pool).await?;
+    let inn_list = load_inn_list(&roles, None, pool, cache).await?;
+    Ok(evaluate_requirements(&roles, char, inn_list.as_deref()))
+}
+// the inn list is only loaded once per evaluation and only if a role needs it
+pub(crate) async fn load_inn_list(
+    roles: &RequirementList,
+    guild_id: Option<i64>,
+    pool: &PgPool,
+    cache: &RequirementCache,
+) -> Result<Option<Arc<RequirementList>>> {
+    if roles.requirements().iter().any(|r| r.req_type == ReqType::Inn) {
+        Ok(Some(cache.inn_list(guild_id, pool).await?))
+    } else {
+        Ok(None)
+    }
+}
+/// Which earned requirements a guild shows, set with `/settings evaluation_mode`.
+#[derive(poise::ChoiceParameter, PartialEq, Clone, Copy, Default, Debug)]
+pub enum EvaluationMode {
+    /// Leaves out requirements that are prereqs of another earned one.
+    #[default]
+    Highest,
+    /// Every earned requirement.
+    All,
+}
+/// Returns the requirements the character has earned, leaving out any that are prereqs of another earned one.
+pub fn evaluate_requirements(
+    roles: &RequirementList,
+    char: &DFCharacterData,
+    inn_list: Option<&RequirementList>,
+) -> RequirementList {
+    evaluate_requirements_with_mode(roles, char, inn_list, EvaluationMode::Highest)
+}
+/// [`evaluate_requirements`] keeping prereqs of earned requirements when `mode` is [`EvaluationMode::All`].
+pub fn evaluate_requirements_with_mode(
+    roles: &RequirementList,
+    char: &DFCharacterData,
+    inn_list: Option<&RequirementList>,
+    mode: EvaluationMode,
+) -> RequirementList {
+    select_requirements(roles, aquired_roles_indexes(roles, char, inn_list), mode)
+}
+/// Requirements earned by any of a member's characters, prereqs are removed across all of them.
+pub fn evaluate_member_requirements(
+    roles: &RequirementList,
+    chars: &[DFCharacterData],
+    inn_list: Option<&RequirementList>,
+    mode: EvaluationMode,
+) -> RequirementList {
+    let mut aquired: Vec<usize> = chars
         .iter()
-        .map(|i| roles.0.swap_remove(*i))
+        .flat_map(|char| aquired_roles_indexes(roles, char, inn_list))
         .collect();
-    aquired_roles.sort_by(|a, b| b.cmp(a));
-    let mut prereq_roles = prereq_roles_to_remove(&roles);
-    prereq_roles.sort();
-    prereq_roles.iter().rev().for_each(|i| {
-        roles.swap_remove(*i);
-    });
-    let mut role_list = RequirementList(roles);
+    aquired.sort_unstable();
+    aquired.dedup();
+    select_requirements(roles, aquired, mode)
+}
+fn select_requirements(
+    roles: &RequirementList,
+    aquired: Vec<usize>,
+    mode: EvaluationMode,
+) -> RequirementList {
+    let aquired_roles: Vec<&Requirement> = aquired.into_iter().map(|i| &roles.0[i]).collect();
+    let prereq_roles = match mode {
+        EvaluationMode::Highest => prereq_roles_to_remove(&aquired_roles),
+        EvaluationMode::All => Vec::new(),
+    };
+    let mut role_list = RequirementList(
+        aquired_roles
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !prereq_roles.contains(i))
+            .map(|(_, role)| role.clone())
+            .collect(),
+    );
     role_list.sort_alphabetical();
-    Ok(role_list)
+    role_list
+}
+/// The amount a ladder rung's `amount` is compared against, `None` for types that aren't counted.
+fn ladder_value(role: &Requirement, char: &DFCharacterData) -> Option<i32> {
+    Some(match role.req_type {
+        ReqType::Gold => char.gold,
+        ReqType::Wars => char.wars.wars().len() as i32,
+        ReqType::Waves => char.wars.war_list().iter().map(|w| w.waves_int()).max().unwrap_or(0),
+        ReqType::ItemUnique | ReqType::ItemLean => char.unique_item_count as i32,
+        ReqType::ItemDC => char.dc_count as i32,
+        ReqType::ItemAmount => {
+            let items = char.item_list.as_ref()?;
+            role.required().iter().filter(|&i| items.contains(i)).count() as i32
+        }
+        _ => return None,
+    })
+}
+/// Reports the highest earned rung of every ladder in `roles` and the character's progress to the next.
+pub fn ladder_progress<'a>(
+    roles: &'a RequirementList,
+    char: &DFCharacterData,
+    inn_list: Option<&RequirementList>,
+) -> Vec<LadderProgress<'a>> {
+    let aquired = aquired_roles_indexes(roles, char, inn_list);
+    let mut ladders: Vec<(&str, Vec<(usize, &Requirement)>)> = Vec::new();
+    for (i, role) in roles.requirements().iter().enumerate() {
+        if let Some(ladder) = &role.ladder {
+            match ladders.iter_mut().find(|(name, _)| name == ladder) {
+                Some((_, rungs)) => rungs.push((i, role)),
+                None => ladders.push((ladder, vec![(i, role)])),
+            }
+        }
+    }
+    ladders
+        .into_iter()
+        .map(|(ladder, mut rungs)| {
+            rungs.sort_by_key(|(_, rung)| rung.amount);
+            let earned = rungs.iter().rposition(|(i, _)| aquired.contains(i));
+            let next = rungs.get(earned.map_or(0, |e| e + 1)).map(|(_, rung)| *rung);
+            LadderProgress {
+                ladder,
+                earned: earned.map(|e| rungs[e].1),
+                next,
+                current: next.and_then(|next| ladder_value(next, char)),
+            }
+        })
+        .collect()
+}
+/// Counts how many members hold each requirement, a member holds it if any of their characters do.
+/// Prereqs of a held requirement are counted as held too.
+pub fn requirement_holders<'a>(
+    roles: &'a RequirementList,
+    members: &[Vec<DFCharacterData>],
+    inn_list: Option<&RequirementList>,
+) -> Vec<(&'a Requirement, usize)> {
+    let mut holders = vec![0; roles.requirements().len()];
+    for chars in members {
+        let mut held: Vec<usize> = chars
+            .iter()
+            .flat_map(|char| aquired_roles_indexes(roles, char, inn_list))
+            .collect();
+        held.sort_unstable();
+        held.dedup();
+        held.into_iter().for_each(|i| holders[i] += 1);
+    }
+    roles.requirements().iter().zip(holders).collect()
+}
+#[derive(Debug, Eq, Hash, PartialEq)]
+enum CacheKey {
+    Guild(i64),
+    Collection(i64, String),
+    Shared(String),
+}
+/// Requirement lists cached in [`crate::Data`] so lookups don't reload them from the database.
+/// Anything that writes requirements or subscriptions must invalidate what it changed.
+/// Clones share the same cache.
+#[derive(Default, Clone)]
+pub struct RequirementCache {
+    inner: Arc<RwLock<CachedLists>>,
+}
+#[derive(Default)]
+struct CachedLists {
+    lists: HashMap<CacheKey, Arc<RequirementList>>,
+    /// Bumped by every invalidation, so a list loaded before one isn't cached after it.
+    generation: u64,
+}
+impl RequirementCache {
+    async fn get_or_load(
+        &self,
+        key: CacheKey,
+        load: impl Future<Output = Result<RequirementList>>,
+    ) -> Result<Arc<RequirementList>> {
+        let generation = {
+            let cache = self.inner.read().await;
+            if let Some(list) = cache.lists.get(&key) {
+                return Ok(Arc::clone(list));
+            }
+            cache.generation
+        };
+        let list = Arc::new(load.await?);
+        let mut cache = self.inner.write().await;
+        if cache.generation == generation {
+            cache.lists.insert(key, Arc::clone(&list));
+        }
+        Ok(list)
+    }
+    /// Cached [`get_requirements`]
+    pub async fn requirements(&self, guild_id: i64, pool: &PgPool) -> Result<Arc<RequirementList>> {
+        self.get_or_load(CacheKey::Guild(guild_id), get_requirements(guild_id, pool))
+            .await
+    }
+    /// Cached [`get_collection`]
+    pub async fn collection(
+        &self,
+        guild_id: i64,
+        collection: &str,
+        pool: &PgPool,
+    ) -> Result<Arc<RequirementList>> {
+        self.get_or_load(
+            CacheKey::Collection(guild_id, collection.to_string()),
+            get_collection(guild_id, collection, pool),
+        )
+        .await
+    }
+    /// Cached [`get_shared_list`]
+    pub async fn shared_list(&self, name: &str, pool: &PgPool) -> Result<Arc<RequirementList>> {
+        self.get_or_load(CacheKey::Shared(name.to_string()), get_shared_list(name, pool))
+            .await
+    }
+    /// Cached [`get_inn_list`]
+    pub async fn inn_list(&self, guild_id: Option<i64>, pool: &PgPool) -> Result<Arc<RequirementList>> {
+        if let Some(guild_id) = guild_id {
+            let inn_list = self.collection(guild_id, INN_COLLECTION, pool).await?;
+            if !inn_list.requirements().is_empty() {
+                return Ok(inn_list);
+            }
+        }
+        self.shared_list(INN_LIST, pool).await
+    }
+    /// Drops everything cached for a guild after its own lists or subscriptions change.
+    pub async fn invalidate_guild(&self, guild_id: i64) {
+        let mut cache = self.inner.write().await;
+        cache.generation = cache.generation.wrapping_add(1);
+        cache.lists.retain(|key, _| match key {
+            CacheKey::Guild(id) | CacheKey::Collection(id, _) => *id != guild_id,
+            CacheKey::Shared(_) => true,
+        });
+    }
+    /// Drops the whole cache, shared lists feed every guild subscribed to them.
+    pub async fn invalidate_all(&self) {
+        let mut cache = self.inner.write().await;
+        cache.generation = cache.generation.wrapping_add(1);
+        cache.lists.clear();
+    }
 }
 
 //TODO
@@ -361,9 +794,16 @@ pub async fn inn_items(
     #[description = "character of selected user"]
     character: Option<i32>,
 ) -> Result<(), Error> {
-    drop(user);
     let pool = &ctx.data().db_connection;
-    let inn_list = get_requirements(INN_GUILD_ID,pool).await?;
+    let character = match (character, user) {
+        (None, Some(user)) => query_with_id(pool, user.id.0).await?,
+        (character, _) => character,
+    };
+    let inn_list = ctx
+        .data()
+        .requirement_cache()
+        .inn_list(ctx.guild_id().map(|id| id.0 as i64), pool)
+        .await?;
     let items = if let Some(df_id) = character {
         let items = CharacterFetcher::new(df_id, LookupCategory::Ascendancies)
             .category(ParsingCategory::Items)
@@ -378,10 +818,93 @@ pub async fn inn_items(
     } else {
         None
     };
-    let pages = get_requirement_pages(inn_list, items);
+    let (pages, categories) = get_requirement_pages(&inn_list, items);
     let (r, g, b) = random_rgb();
     let embed = PaginateEmbed::new("Inn Items", None, Color::from_rgb(r, g, b), pages)
+        .set_categories(categories)
         .set_empty_string("No Inn Items to display");
     paginate(ctx, embed).await?;
     Ok(())
 }
+/// Every member of a guild, fetched from Discord since the cache only holds those sent on connect.
+pub async fn guild_member_ids(ctx: Context<'_>, guild_id: i64) -> Result<Vec<i64>> {
+    let mut members = Box::pin(GuildId(guild_id as u64).members_iter(ctx.http()));
+    let mut member_ids = Vec::new();
+    while let Some(member) = members.next().await {
+        member_ids.push(member?.user.id.0 as i64);
+    }
+    Ok(member_ids)
+}
+/// Fetches the items of every character registered to the given members, grouped by discord id.
+pub async fn fetch_member_characters(
+    member_ids: &[i64],
+    pool: &PgPool,
+) -> Result<HashMap<i64, Vec<DFCharacterData>>> {
+    let registered = query!(
+        "SELECT discord_id, df_id FROM df_characters WHERE discord_id = any($1)",
+        member_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    let df_ids: Vec<i32> = registered.iter().map(|r| r.df_id).collect();
+    let mut members: HashMap<i64, Vec<DFCharacterData>> = HashMap::new();
+    for char in fetch_characters_with_items(&df_ids).await {
+        if let Some(r) = registered.iter().find(|r| r.df_id == char.id) {
+            members.entry(r.discord_id).or_default().push(char);
+        }
+    }
+    Ok(members)
+}
+/// Number of rare roles highlighted at the top of [`role_stats`].
+const RAREST_ROLES: usize = 5;
+/// Show how many members hold each of this server's roles
+#[poise::command(slash_command, guild_only)]
+pub async fn role_stats(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = match guild_only_with_id(ctx).await? {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    ctx.defer().await?;
+    let pool = ctx.data().db();
+    let guild_name = ctx.guild().unwrap().name;
+    let member_ids = guild_member_ids(ctx, guild_id).await?;
+    let members: Vec<Vec<DFCharacterData>> = fetch_member_characters(&member_ids, pool)
+        .await?
+        .into_values()
+        .collect();
+    let cache = ctx.data().requirement_cache();
+    let roles = cache.requirements(guild_id, pool).await?;
+    let inn_list = load_inn_list(&roles, Some(guild_id), pool, cache).await?;
+    let mut holders = requirement_holders(&roles, &members, inn_list.as_deref());
+    holders.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.name().cmp(b.name())));
+    let percent = |count: usize| count as f64 / members.len().max(1) as f64 * 100.0;
+    let mut pages = vec![String::new()];
+    let (mut current_len, mut current_page) = (0, 0);
+    let header = format!(
+        "Evaluated **{}** characters of **{}** members\n\n__**Rarest Roles**__\n",
+        members.iter().map(Vec::len).sum::<usize>(),
+        members.len()
+    );
+    paginate_item(&mut pages, header, &mut current_len, &mut current_page);
+    let rarest = holders.iter().rev().filter(|(_, count)| *count > 0).take(RAREST_ROLES);
+    for (role, count) in rarest {
+        let line = format!("**{}** {:.1}%\n", role.name(), percent(*count));
+        paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+    }
+    paginate_item(
+        &mut pages,
+        "\n__**All Roles**__\n".to_string(),
+        &mut current_len,
+        &mut current_page,
+    );
+    for (role, count) in holders.iter() {
+        let mut line = format!("**{}** {}/{}", role.name(), count, members.len());
+        let _ = writeln!(line, " ({:.1}%)", percent(*count));
+        paginate_item(&mut pages, line, &mut current_len, &mut current_page);
+    }
+    let title = format!("{guild_name}'s Role Stats");
+    let (r, g, b) = random_rgb();
+    let embed = PaginateEmbed::new(&title, None, Color::from_rgb(r, g, b), pages);
+    paginate(ctx, embed).await?;
+    Ok(())
+}
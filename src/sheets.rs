@@ -1,89 +1,345 @@
-use crate::parsing::DFCharacterData;
-use crate::{lookup_df::LookupState, parsing::Items};
+use crate::parsing::{DFCharacterData, ItemTag, Items};
 use color_eyre::Result;
+use num_format::{Locale, ToFormattedString};
 use rust_xlsxwriter::*;
-fn write_items_to_sheet(
-    worksheet: &mut Worksheet,
-    list: &Items,
-    row: u16,
-    name: &str,
-) -> Result<()> {
-    let mut spacing = 0;
-    worksheet.write_string(0, row, name)?;
-    worksheet.write_string(2, row, format!("Unique Amount: {}", list.count()))?;
-    for (i, list) in list.split_list().enumerate() {
-        let list_len = list.len();
-        worksheet.write_string(3 + spacing, row, Items::text(i))?;
-        worksheet.write_column(5 + spacing, row, list)?;
-        spacing += 4 + list_len as u32;
+use std::collections::BTreeMap;
+use std::fmt;
+/// Doom Knight ranks from lowest to highest.
+const DMK_RANKS: [&str; 4] = [
+    "Doom Knight",
+    "Master Doom Knight",
+    "Superior Doom Knight",
+    "Elite Doom Knight",
+];
+/// How one character's items compare to the others.
+#[derive(Debug)]
+pub struct CharacterItems {
+    pub df_id: i32,
+    pub name: String,
+    /// Items none of the other characters have.
+    pub unique: Vec<String>,
+    /// Items every other character has but this one.
+    pub missing: Vec<String>,
+}
+/// An item's row in the matrix, `amounts` has one entry per character in comparison order.
+#[derive(Debug)]
+pub struct ItemRow {
+    pub tag: ItemTag,
+    pub amounts: Vec<i32>,
+}
+impl ItemRow {
+    pub fn owners(&self) -> usize {
+        self.amounts.iter().filter(|a| **a > 0).count()
     }
-    Ok(())
 }
-fn extract_data(lookup_state: LookupState) -> Option<DFCharacterData> {
-    match lookup_state {
-        LookupState::CharacterPage(data) => Some(data),
-        _ => None,
+#[derive(Debug)]
+pub struct ItemComparison {
+    pub characters: Vec<CharacterItems>,
+    /// Items every character has.
+    pub shared: Vec<String>,
+    /// Every item any of the characters has, by name.
+    pub matrix: BTreeMap<String, ItemRow>,
+}
+impl ItemComparison {
+    pub fn names(&self) -> Vec<&str> {
+        self.characters.iter().map(|c| c.name.as_str()).collect()
     }
 }
+#[derive(PartialEq, Debug)]
+pub enum StatValue {
+    Number(i64),
+    Text(String),
+    Missing,
+}
+impl fmt::Display for StatValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatValue::Number(n) => write!(f, "{}", n.to_formatted_string(&Locale::en)),
+            StatValue::Text(text) => write!(f, "{text}"),
+            StatValue::Missing => write!(f, "-"),
+        }
+    }
+}
+/// A stat for every character in comparison order.
+#[derive(Debug)]
+pub struct StatRow {
+    pub label: String,
+    pub values: Vec<StatValue>,
+    /// The characters with the best value, empty when every character ties.
+    pub winners: Vec<usize>,
+}
+impl StatRow {
+    fn new<T: Ord + Copy>(
+        label: impl Into<String>,
+        chars: &[DFCharacterData],
+        value: impl Fn(&DFCharacterData) -> StatValue,
+        score: impl Fn(&DFCharacterData) -> T,
+    ) -> Self {
+        let scores: Vec<T> = chars.iter().map(score).collect();
+        let winners = match scores.iter().max() {
+            Some(best) if scores.iter().any(|s| s != best) => {
+                (0..scores.len()).filter(|i| scores[*i] == *best).collect()
+            }
+            _ => Vec::new(),
+        };
+        StatRow {
+            label: label.into(),
+            values: chars.iter().map(value).collect(),
+            winners,
+        }
+    }
+}
+/// The characters' stats side by side and the waves of every war any of them fought in.
+#[derive(Debug)]
+pub struct StatComparison {
+    pub stats: Vec<StatRow>,
+    pub wars: Vec<StatRow>,
+}
 pub struct SheetData {
-    pub user_one_name: String,
-    pub user_two_name: String,
-    pub user_one_unique_dif: u16,
-    pub user_two_unique_dif: u16,
+    pub comparison: ItemComparison,
+    pub stats: StatComparison,
     pub buf: Vec<u8>,
 }
-
-pub async fn compare_sheet(
-    main_state: LookupState,
-    second_state: LookupState,
-) -> Result<Option<SheetData>> {
-    let mut workbook = Workbook::new();
-
-    let mut char1 = extract_data(main_state);
-    let mut char2 = extract_data(second_state);
-
-    let (mut char1, mut char2) = match (char1.take(), char2.take()) {
-        (Some(c1), Some(c2)) => (c1, c2),
-        _ => return Ok(None),
+fn dmk_rank(char: &DFCharacterData) -> usize {
+    char.dmk
+        .as_deref()
+        .and_then(|dmk| DMK_RANKS.iter().position(|rank| *rank == dmk))
+        .map_or(0, |i| i + 1)
+}
+/// Compares the characters' stats, higher is better and owning a dragon beats not owning one.
+pub fn compare_stats(chars: &[DFCharacterData]) -> StatComparison {
+    let number = |n: i64| StatValue::Number(n);
+    let stats = vec![
+        StatRow::new("Level", chars, |c| number(c.level.into()), |c| c.level),
+        StatRow::new("Gold", chars, |c| number(c.gold.into()), |c| c.gold),
+        StatRow::new(
+            "DA Items",
+            chars,
+            |c| number(c.da_count.into()),
+            |c| c.da_count,
+        ),
+        StatRow::new(
+            "DC Items",
+            chars,
+            |c| number(c.dc_count.into()),
+            |c| c.dc_count,
+        ),
+        StatRow::new(
+            "NDA Items",
+            chars,
+            |c| number(c.nda_count.into()),
+            |c| c.nda_count,
+        ),
+        StatRow::new(
+            "Artifacts",
+            chars,
+            |c| number(c.artifact_count.into()),
+            |c| c.artifact_count,
+        ),
+        StatRow::new(
+            "Total Waves",
+            chars,
+            |c| number(c.wars.calc_waves_cleared().into()),
+            |c| c.wars.calc_waves_cleared(),
+        ),
+        StatRow::new(
+            "Dragon",
+            chars,
+            |c| match &c.dragon {
+                Some(dragon) => {
+                    StatValue::Text(format!("{} ({})", dragon.name, dragon.dragon_type))
+                }
+                None => StatValue::Missing,
+            },
+            |c| c.dragon.is_some(),
+        ),
+        StatRow::new(
+            "DMK",
+            chars,
+            |c| c.dmk.clone().map_or(StatValue::Missing, StatValue::Text),
+            dmk_rank,
+        ),
+    ];
+    let mut war_labels: Vec<&str> = Vec::new();
+    for war in chars.iter().flat_map(|c| c.wars.wars()) {
+        if !war_labels.contains(&war.warlabel.as_str()) {
+            war_labels.push(&war.warlabel);
+        }
+    }
+    let waves = |char: &DFCharacterData, label: &str| {
+        char.wars
+            .wars()
+            .iter()
+            .find(|w| w.warlabel == label)
+            .map(|w| w.waves_int())
     };
-
+    let wars = war_labels
+        .into_iter()
+        .map(|label| {
+            StatRow::new(
+                label,
+                chars,
+                |c| waves(c, label).map_or(StatValue::Missing, |w| number(w.into())),
+                |c| waves(c, label).unwrap_or(0),
+            )
+        })
+        .collect();
+    StatComparison { stats, wars }
+}
+/// Compares the items of the characters, characters without an item list own nothing.
+pub fn compare_items(chars: &[DFCharacterData]) -> ItemComparison {
+    let empty = Items::new();
+    let mut matrix: BTreeMap<String, ItemRow> = BTreeMap::new();
+    for (i, char) in chars.iter().enumerate() {
+        for (name, item) in char.item_list.as_ref().unwrap_or(&empty).items() {
+            let row = matrix.entry(name.clone()).or_insert_with(|| ItemRow {
+                tag: item.tag,
+                amounts: vec![0; chars.len()],
+            });
+            row.amounts[i] = item.amount;
+        }
+    }
+    let mut characters: Vec<CharacterItems> = chars
+        .iter()
+        .map(|c| CharacterItems {
+            df_id: c.id,
+            name: c.name.clone(),
+            unique: Vec::new(),
+            missing: Vec::new(),
+        })
+        .collect();
+    let mut shared = Vec::new();
+    for (name, row) in &matrix {
+        let owners = row.owners();
+        if owners == chars.len() {
+            shared.push(name.clone());
+            continue;
+        }
+        // with two characters an item unique to one is missing only from the other
+        if owners == 1 {
+            if let Some(i) = row.amounts.iter().position(|a| *a > 0) {
+                characters[i].unique.push(name.clone());
+            }
+        }
+        if owners == chars.len() - 1 {
+            if let Some(i) = row.amounts.iter().position(|a| *a == 0) {
+                characters[i].missing.push(name.clone());
+            }
+        }
+    }
+    ItemComparison {
+        characters,
+        shared,
+        matrix,
+    }
+}
+/// One row per item and one column per character with the amount owned.
+fn write_matrix(worksheet: &mut Worksheet, comparison: &ItemComparison) -> Result<()> {
+    let header = Format::new().set_bold();
+    let chars = comparison.characters.len() as u16;
+    worksheet.set_name("Items")?;
+    worksheet.write_string_with_format(0, 0, "Item", &header)?;
+    worksheet.write_string_with_format(0, 1, "Type", &header)?;
+    for (i, name) in comparison.names().into_iter().enumerate() {
+        worksheet.write_string_with_format(0, 2 + i as u16, name, &header)?;
+    }
+    worksheet.write_string_with_format(0, 2 + chars, "Owned By", &header)?;
+    for (i, (name, row)) in comparison.matrix.iter().enumerate() {
+        let sheet_row = 1 + i as u32;
+        worksheet.write_string(sheet_row, 0, name)?;
+        worksheet.write_string(sheet_row, 1, row.tag.to_string())?;
+        for (col, amount) in row.amounts.iter().enumerate() {
+            if *amount > 0 {
+                worksheet.write_number(sheet_row, 2 + col as u16, *amount)?;
+            }
+        }
+        worksheet.write_number(sheet_row, 2 + chars, row.owners() as u32)?;
+    }
+    worksheet.set_freeze_panes(1, 1)?;
+    worksheet.autofilter(0, 0, comparison.matrix.len() as u32, 2 + chars)?;
+    worksheet.autofit();
+    Ok(())
+}
+/// One row per stat and war and one column per character, winners are highlighted.
+fn write_stats(worksheet: &mut Worksheet, names: &[&str], stats: &StatComparison) -> Result<()> {
+    let header = Format::new().set_bold();
+    let plain = Format::new();
+    let winner = Format::new()
+        .set_bold()
+        .set_background_color(Color::RGB(0xC6EFCE));
+    worksheet.set_name("Stats")?;
+    worksheet.write_string_with_format(0, 0, "Stat", &header)?;
+    for (i, name) in names.iter().enumerate() {
+        worksheet.write_string_with_format(0, 1 + i as u16, *name, &header)?;
+    }
+    for (i, row) in stats.stats.iter().chain(&stats.wars).enumerate() {
+        let sheet_row = 1 + i as u32;
+        worksheet.write_string_with_format(sheet_row, 0, &row.label, &header)?;
+        for (col, value) in row.values.iter().enumerate() {
+            let col = 1 + col as u16;
+            let format = if row.winners.contains(&(col as usize - 1)) {
+                &winner
+            } else {
+                &plain
+            };
+            match value {
+                StatValue::Number(n) => {
+                    worksheet.write_number_with_format(sheet_row, col, *n as f64, format)?
+                }
+                StatValue::Text(text) => {
+                    worksheet.write_string_with_format(sheet_row, col, text, format)?
+                }
+                StatValue::Missing => worksheet.write_blank(sheet_row, col, format)?,
+            };
+        }
+    }
+    worksheet.set_freeze_panes(1, 1)?;
+    worksheet.autofit();
+    Ok(())
+}
+/// Each character's unique and missing items, then the items shared by all.
+fn write_summary(worksheet: &mut Worksheet, comparison: &ItemComparison) -> Result<()> {
+    let header = Format::new().set_bold();
+    worksheet.set_name("Summary")?;
+    for (i, char) in comparison.characters.iter().enumerate() {
+        let col = 2 * i as u16;
+        worksheet.write_string_with_format(0, col, &char.name, &header)?;
+        worksheet.write_string(1, col, format!("Unique ({})", char.unique.len()))?;
+        worksheet.write_column(2, col, &char.unique)?;
+        worksheet.write_string(1, col + 1, format!("Missing ({})", char.missing.len()))?;
+        worksheet.write_column(2, col + 1, &char.missing)?;
+    }
+    let col = 2 * comparison.characters.len() as u16;
+    worksheet.write_string_with_format(0, col, "Shared By All", &header)?;
+    worksheet.write_string(1, col, format!("Items ({})", comparison.shared.len()))?;
+    worksheet.write_column(2, col, &comparison.shared)?;
+    worksheet.autofit();
+    Ok(())
+}
+/// Compares the characters' stats and items and writes them to a workbook
+/// of a stat table, an item matrix and an item summary.
+pub fn compare_sheet(chars: &[DFCharacterData]) -> Result<SheetData> {
+    let comparison = compare_items(chars);
+    let stats = compare_stats(chars);
+    let names = comparison.names().join(" vs ");
+    let mut workbook = Workbook::new();
     let properties = DocProperties::new()
-        .set_title(format!("{} vs {}", char1.name, char2.name))
-        .set_subject(format!("Comparing {} and {}", char1.name, char2.name))
+        .set_title(&names)
+        .set_subject(format!("Comparing {names}"))
         .set_author("Achivit")
         .set_manager("Ruleofnine")
         .set_company("Rust Solutions Inc")
         .set_category("Dragonfable compare characters")
         .set_keywords("DF,Compare,Uniques")
         .set_comment("Created with Rust and rust_xlsxwriter");
-
     workbook.set_properties(&properties);
-
-    let worksheet = workbook.add_worksheet();
-
-    if let (Some(mut list1), Some(mut list2)) = (char1.item_list.take(), char2.item_list.take()) {
-        let list1_keys: Vec<_> = list1.items().keys().cloned().collect();
-        let list2_keys: Vec<_> = list2.items().keys().cloned().collect();
-
-        // Retain only unique items in list1
-        list1.items_mut().retain(|k, _| !list2_keys.contains(k));
-
-        // Retain only unique items in list2
-        list2.items_mut().retain(|k, _| !list1_keys.contains(k));
-        write_items_to_sheet(worksheet, &list1, 0, &char1.name)?;
-        write_items_to_sheet(worksheet, &list2, 5, &char2.name)?;
-
-        let buf = workbook.save_to_buffer()?;
-        let sheet_data = SheetData {
-            user_one_name: char1.name.to_owned(),
-            user_two_name: char2.name.to_owned(),
-            user_one_unique_dif: list1.count(),
-            user_two_unique_dif: list2.count(),
-            buf,
-        };
-
-        return Ok(Some(sheet_data));
-    }
-
-    Ok(None)
+    write_stats(workbook.add_worksheet(), &comparison.names(), &stats)?;
+    write_matrix(workbook.add_worksheet(), &comparison)?;
+    write_summary(workbook.add_worksheet(), &comparison)?;
+    let buf = workbook.save_to_buffer()?;
+    Ok(SheetData {
+        comparison,
+        stats,
+        buf,
+    })
 }
@@ -76,7 +76,7 @@ pub async fn server_time(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer().await?;
     let now = Local::now();
     let avatar_url = ctx.serenity_context().cache.current_user().face();
-    let description = format!(
+    let mut description = format!(
         "**{}, {}, {:02}{} {:04}** \n**{:02}:{:02}:{:02} {} EST**\n**Week:** {} **Day:** {:03}\n**Swatch Time:** @{:03.0}\n**Day Progress:** {:.2}%\n**Seconds Since Midnight:** {}\n**Seconds Until Midnight:** {}",
         now.format("%A"),
         now.format("%B"),
@@ -94,6 +94,13 @@ pub async fn server_time(ctx: Context<'_>) -> Result<(), Error> {
         seconds_since_midnight(),
         seconds_until_midnight(),
     );
+    if let Some(guild_id) = ctx.guild_id() {
+        let config = ctx.data().guild_config().get(guild_id.0 as i64, ctx.data().db()).await?;
+        if let Some(tz) = config.timezone() {
+            let local = Utc::now().with_timezone(&tz);
+            description.push_str(&format!("\n**{tz}:** {}", local.format("%I:%M:%S %p %Z")));
+        }
+    }
     ctx.send(|f| {
         f.embed(|f| {
             f.color(random_rgb())
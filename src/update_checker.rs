@@ -1,8 +1,9 @@
+use crate::announcements::{get_all_targets, get_guild_targets, AnnouncementTarget, UpdateFeed};
+use crate::audit::{audit_global_change, AuditAction};
 use crate::parsing::ElementRefWrapper;
 use crate::{
     embeds::send_update_embed,
-    guild_settings::GuildSettings,
-    requests::{fetch_page_with_user_agent, DESIGN_NOTES_LINK, USER_AGENT},
+    requests::{fetch_page_with_user_agent, USER_AGENT},
     Context, Error, Task,
 };
 use chrono::{Datelike, NaiveDate};
@@ -10,7 +11,6 @@ use color_eyre::{eyre::eyre, Result};
 use getset::Getters;
 use log::{error, info};
 use scraper::{Html, Selector};
-use sqlx::{query_as, FromRow};
 use std::sync::Arc;
 use tokio::time::{self, Duration};
 static DATE_FORMATS: [&str; 27] = [
@@ -54,6 +54,7 @@ const UPDATE_CHECKER: &str = "update_checker";
 #[derive(Debug, Getters)]
 #[get = "pub"]
 pub struct DesignNote {
+    feed: UpdateFeed,
     update_name: String,
     link: String,
     date: NaiveDate,
@@ -63,6 +64,7 @@ pub struct DesignNote {
 }
 impl DesignNote {
     fn new(
+        feed: UpdateFeed,
         update_name: String,
         date: NaiveDate,
         link: String,
@@ -71,6 +73,7 @@ impl DesignNote {
         poster_image: String,
     ) -> DesignNote {
         DesignNote {
+            feed,
             update_name,
             date,
             link,
@@ -80,7 +83,8 @@ impl DesignNote {
         }
     }
 
-    pub fn parse_from_str(dn_str: &str) -> Result<DesignNote> {
+    /// Parses the newest note on a page of `feed`'s design notes.
+    pub fn parse_from_str(dn_str: &str, feed: UpdateFeed) -> Result<DesignNote> {
         let document = Html::parse_document(dn_str);
         let article_selector = Selector::parse("div.col.pt-2.dn-article").unwrap();
         let article = document
@@ -152,7 +156,7 @@ impl DesignNote {
             .value()
             .attr("src")
             .ok_or(eyre!("Src attribute not found"))?;
-        let poster_image = format!("https://www.dragonfable.com{}", poster_image);
+        let poster_image = format!("{}{}", feed.site(), poster_image);
 
         let poster_name_selector =
             Selector::parse("div.d-none.d-md-block.dnAvatar.col-auto.text-center.pt-2 p").unwrap();
@@ -162,6 +166,7 @@ impl DesignNote {
             .ok_or(eyre!("Unable to find poster name"))?
             .inner_html();
         Ok(DesignNote::new(
+            feed,
             update_name,
             parsed_date,
             link.to_string(),
@@ -171,57 +176,72 @@ impl DesignNote {
         ))
     }
 }
+/// Fetches and parses the newest design note of a feed this month.
+async fn fetch_design_note(feed: UpdateFeed) -> Result<DesignNote> {
+    let now = chrono::Local::now();
+    let url = feed.design_notes_url(now.year(), now.month());
+    let page = fetch_page_with_user_agent(USER_AGENT, &url).await?;
+    DesignNote::parse_from_str(&page, feed)
+}
 async fn run_update_checker(
     ctx: Context<'_>,
     flag: UpdateCheckerFeatureFlag,
-    all_guilds: Arc<Vec<GuildSettings>>,
+    targets: Arc<Vec<AnnouncementTarget>>,
 ) -> Result<()> {
     let tasks = ctx.data().tasks().clone_inner();
-    let now = chrono::Local::now();
-    let dn_url = format!("{DESIGN_NOTES_LINK}/{}/{}",now.year(),now.month());
-    let last_dn_str = fetch_page_with_user_agent(USER_AGENT, &dn_url).await?;
-    let last_dn = DesignNote::parse_from_str(&last_dn_str)?;
+    // the newest note of each feed when the checker started, `None` until a feed is first fetched
+    let mut last_dates: Vec<(UpdateFeed, Option<NaiveDate>)> = Vec::new();
+    for feed in UpdateFeed::ALL {
+        let last_date = match fetch_design_note(feed).await {
+            Ok(note) => Some(*note.date()),
+            Err(e) => {
+                error!("Failed to fetch {feed} DN with error: [{e}]");
+                None
+            }
+        };
+        last_dates.push((feed, last_date));
+    }
+    if last_dates.iter().all(|(_, date)| date.is_none()) {
+        return Err(eyre!("Unable to fetch any design notes"));
+    }
+    let force = flag == UpdateCheckerFeatureFlag::Force || flag == UpdateCheckerFeatureFlag::ForceNoPing;
     tokio::spawn(async move {
         let mut interval = time::interval(Duration::from_secs(10));
         while tasks.is_running(UPDATE_CHECKER).await {
             interval.tick().await;
-            let new_dn_str = match fetch_page_with_user_agent(USER_AGENT, &dn_url).await {
-                Ok(data) => data,
-                Err(e) => {
-                    error!("Failed to fetch page for DN with error: [{e}]");
-                    continue;
-                }
-            };
-            let new_dn = match DesignNote::parse_from_str(&new_dn_str) {
-                Ok(note) => note,
-                Err(e) => {
-                    error!("failed to parse DN with error: [{e}]");
-                    continue;
+            let mut sent = false;
+            for (feed, last_date) in last_dates.iter_mut() {
+                let new_dn = match fetch_design_note(*feed).await {
+                    Ok(note) => note,
+                    Err(e) => {
+                        error!("Failed to fetch {feed} DN with error: [{e}]");
+                        continue;
+                    }
+                };
+                info!("last {feed} dn: {last_date:?}");
+                info!("new {feed} dn: {}", new_dn.date());
+                let is_new = match last_date {
+                    Some(date) => new_dn.date() > date,
+                    // first successful fetch of this feed, nothing to compare against yet
+                    None => {
+                        *last_date = Some(*new_dn.date());
+                        false
+                    }
+                };
+                if is_new || force {
+                    sent = true;
+                    if let Err(e) = send_update_embed(Arc::clone(&targets), new_dn, flag).await {
+                        error!("Failed to send Embed for {feed} DN with error: [{e}]")
+                    }
                 }
-            };
-            info!("last dn: {}",last_dn.date());
-            info!("new dn: {}",new_dn.date());
-            if new_dn.date() > last_dn.date() || flag == UpdateCheckerFeatureFlag::Force || flag == UpdateCheckerFeatureFlag::ForceNoPing {
+            }
+            if sent {
                 tasks.stop_task(UPDATE_CHECKER).await;
-                if let Err(e) = send_update_embed(Arc::clone(&all_guilds), new_dn,flag).await {
-                    error!("Failed to send Embed for DN with error: [{e}]")
-                }
-                // send message to channel with ctx.send()
             }
         }
     });
     Ok(())
 }
-#[derive(FromRow, Debug)]
-struct AnnouncementSettings {
-    announcement_channel_id: Option<i64>,
-    announcement_role_id: Option<i64>,
-}
-impl AnnouncementSettings {
-    fn is_set(&self) -> bool {
-        self.announcement_channel_id.is_some() && self.announcement_role_id.is_some()
-    }
-}
 /// Check Design Notes for update every 10 seconds
 #[poise::command(
     slash_command,
@@ -234,39 +254,16 @@ pub async fn update_checker(ctx: Context<'_>, flag: UpdateCheckerFeatureFlag) ->
     let pool = ctx.data().db();
     let guild_id = ctx.guild_id().unwrap().0 as i64;
     let is_running = tasks.is_running(UPDATE_CHECKER).await;
-    let prefix = ctx
-        .framework()
-        .options()
-        .prefix_options
-        .prefix
-        .as_deref()
-        .unwrap_or_default();
-    let guild_settings = query_as!(AnnouncementSettings,"select announcement_channel_id,announcement_role_id from guild_settings where guild_id = $1",guild_id)
-        .fetch_optional(pool).await?;
-    match guild_settings {
-        Some(settings) => {
-            if !settings.is_set() {
-                {
-                    ctx.reply(format!("No Announcement Settings for this guild\nUse \"{prefix}init_announcements #channel @role\" to set up Announcements for this guild")).await?;
-                    return Ok(());
-                }
-            }
-            settings
-        }
-        None => {
-            ctx.reply(
-                format!("No Guild registered with this Guild ID.\n\"{prefix}init_guild\" to register Guild."),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
-    let all_guilds = query_as!(GuildSettings,"select * from guild_settings where announcement_channel_id IS NOT NULL and announcement_role_id IS NOT NULL").fetch_all(pool).await?;
-    let arc_guilds = Arc::new(all_guilds);
+    if get_guild_targets(guild_id, pool).await?.is_empty() {
+        ctx.reply("No Announcement Channels for this guild\nUse \"/announcements add\" to set up Announcements for this guild").await?;
+        return Ok(());
+    }
+    let arc_targets = Arc::new(get_all_targets(pool).await?);
     match (&flag, is_running) {
         (UpdateCheckerFeatureFlag::Start, false) | (UpdateCheckerFeatureFlag::Force, _) | (UpdateCheckerFeatureFlag::ForceNoPing, _) => {
             tasks.start_task(UPDATE_CHECKER).await;
-            run_update_checker(ctx, flag, arc_guilds).await?;
+            run_update_checker(ctx, flag, arc_targets).await?;
+            audit_global_change(ctx, AuditAction::UpdateChecker, Some(format!("Running: {is_running}")), Some(format!("Started with {flag}"))).await?;
             ctx.reply(format!("Command Executed Successfully with Feature Flag {}",flag)).await?;
         }
         (UpdateCheckerFeatureFlag::Start, true) => {
@@ -274,6 +271,7 @@ pub async fn update_checker(ctx: Context<'_>, flag: UpdateCheckerFeatureFlag) ->
         }
         (UpdateCheckerFeatureFlag::Stop, true) => {
             tasks.stop_task(UPDATE_CHECKER).await;
+            audit_global_change(ctx, AuditAction::UpdateChecker, Some("Running: true".to_owned()), Some("Stopped".to_owned())).await?;
             ctx.reply("Update Checker is **STOPPED!**").await?;
         }
         (UpdateCheckerFeatureFlag::Stop, false) => {
@@ -0,0 +1,78 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        announcements::{
+            get_all_targets, get_guild_targets, parse_keywords, remove_target, upsert_target,
+            UpdateFeed,
+        },
+        db::{establish_connection, mark_guild_inactive, purge_inactive_guilds},
+        guild_settings::set_announcements,
+        update_checker::DesignNote,
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    #[tokio::test]
+    async fn db_announcement_targets_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        set_announcements(4, "announce_test", Some(10), Some(20), &pool).await?;
+        let keywords = parse_keywords(" Boss, ,WAR ");
+        assert_eq!(keywords, vec!["boss", "war"]);
+        let filtered = upsert_target(4, 11, None, Some(UpdateFeed::DragonFable), &keywords, &pool).await?;
+        assert!(filtered.matches(UpdateFeed::DragonFable, "New War: The Doom Boss"));
+        assert!(!filtered.matches(UpdateFeed::DragonFable, "Weekly Release"));
+        assert!(!filtered.matches(UpdateFeed::AdventureQuestWorlds, "Boss Rush"));
+        let targets = get_guild_targets(4, &pool).await?;
+        assert_eq!(targets.len(), 2);
+        assert!(targets[0].matches(UpdateFeed::AdventureQuestWorlds, "anything"));
+        // moving the announcement channel replaces its target
+        set_announcements(4, "announce_test", Some(12), None, &pool).await?;
+        let channels: Vec<i64> = get_guild_targets(4, &pool)
+            .await?
+            .iter()
+            .map(|t| *t.channel_id())
+            .collect();
+        assert_eq!(channels, vec![11, 12]);
+        assert!(remove_target(4, 12, &pool).await?);
+        assert!(!remove_target(4, 12, &pool).await?);
+        query!("delete from guild_settings where guild_id = 4").execute(&pool).await?;
+        Ok(())
+    }
+    #[tokio::test]
+    async fn db_feed_target_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        set_announcements(11, "feed_test", Some(110), None, &pool).await?;
+        let page = std::fs::read_to_string("htmls/dn12-15.html")?;
+        let note = DesignNote::parse_from_str(&page, UpdateFeed::AdventureQuestWorlds)?;
+        assert_eq!(*note.feed(), UpdateFeed::AdventureQuestWorlds);
+        assert!(note.poster_image().starts_with("https://www.aq.com/"));
+        let aqw = upsert_target(11, 111, None, Some(UpdateFeed::AdventureQuestWorlds), &[], &pool).await?;
+        let df = upsert_target(11, 112, None, Some(UpdateFeed::DragonFable), &[], &pool).await?;
+        let all = get_guild_targets(11, &pool).await?;
+        assert!(aqw.matches_note(&note));
+        assert!(!df.matches_note(&note));
+        assert!(all.iter().find(|t| *t.channel_id() == 110).unwrap().matches_note(&note));
+        query!("delete from guild_settings where guild_id = 11").execute(&pool).await?;
+        Ok(())
+    }
+    #[tokio::test]
+    async fn db_inactive_guild_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        set_announcements(7, "inactive_test", Some(70), None, &pool).await?;
+        assert!(get_all_targets(&pool).await?.iter().any(|t| *t.guild_id() == 7));
+        assert!(mark_guild_inactive(&pool, 7).await?);
+        // already inactive, so a second removal isn't reported again
+        assert!(!mark_guild_inactive(&pool, 7).await?);
+        assert!(!get_all_targets(&pool).await?.iter().any(|t| *t.guild_id() == 7));
+        // still within the retention period
+        purge_inactive_guilds(&pool, 1).await?;
+        assert_eq!(get_guild_targets(7, &pool).await?.len(), 1);
+        purge_inactive_guilds(&pool, 0).await?;
+        assert!(get_guild_targets(7, &pool).await?.is_empty());
+        Ok(())
+    }
+}
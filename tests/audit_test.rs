@@ -0,0 +1,86 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        audit::{get_audit_log, record_audit, AuditAction},
+        db::establish_connection,
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    #[tokio::test]
+    async fn db_audit_log_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        let after = Some("audit_char (991) registered to <@99>".to_owned());
+        record_audit(
+            &pool,
+            Some(9),
+            Some(99),
+            "admin",
+            AuditAction::CharacterRegister,
+            None,
+            after,
+        )
+        .await?;
+        let before = Some("Prefix: default".to_owned());
+        let after = Some("Prefix: !".to_owned());
+        record_audit(
+            &pool,
+            Some(9),
+            Some(98),
+            "other_admin",
+            AuditAction::SettingsChange,
+            before,
+            after,
+        )
+        .await?;
+        record_audit(
+            &pool,
+            Some(9),
+            None,
+            "Discord",
+            AuditAction::GuildLeave,
+            None,
+            None,
+        )
+        .await?;
+        let entries = get_audit_log(&pool, Some(9), None, None).await?;
+        assert_eq!(entries.len(), 3);
+        // newest first
+        assert_eq!(entries[0].action(), "Guild Leave");
+        let by_actor = get_audit_log(&pool, Some(9), Some(98), None).await?;
+        assert_eq!(by_actor.len(), 1);
+        assert_eq!(by_actor[0].after().as_deref(), Some("Prefix: !"));
+        let by_action = get_audit_log(&pool, Some(9), None, Some(AuditAction::CharacterRegister)).await?;
+        assert_eq!(*by_action[0].actor_id(), Some(99));
+        assert!(
+            get_audit_log(&pool, Some(9), Some(98), Some(AuditAction::GuildLeave))
+                .await?
+                .is_empty()
+        );
+        // bot-wide changes are kept apart from every guild's
+        record_audit(
+            &pool,
+            None,
+            Some(100),
+            "super_user",
+            AuditAction::ListDelete,
+            Some("Shared list audit_list".to_owned()),
+            None,
+        )
+        .await?;
+        assert_eq!(get_audit_log(&pool, Some(9), None, None).await?.len(), 3);
+        let global = get_audit_log(&pool, None, Some(100), None).await?;
+        assert_eq!(global.len(), 1);
+        assert_eq!(global[0].action(), "List Delete");
+        assert_eq!(*global[0].guild_id(), None);
+        query!("delete from audit_log where guild_id is null and actor_id = 100")
+            .execute(&pool)
+            .await?;
+        query!("delete from audit_log where guild_id = 9")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        backup::{export_guild, restore_guild, GuildArchive, ARCHIVE_VERSION},
+        db::establish_connection,
+        guild_settings::{insert_requirements, set_announcements},
+        requirements::{get_collection, get_requirements_file, RequirementCache},
+        requirement_lists::ROLES_LIST,
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    #[tokio::test]
+    async fn db_backup_restore_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        let cache = RequirementCache::default();
+        set_announcements(5, "backup_test", Some(50), Some(51), &pool).await?;
+        let roles = get_requirements_file("roles.json")?;
+        insert_requirements(5, &pool, &cache, &roles).await?;
+        query!("insert into users (discord_name, discord_id, registered_by) VALUES ('backup_user', 55, 'test')").execute(&pool).await?;
+        query!("insert into df_characters (discord_id, df_id, character_name, registered_by) VALUES (55, 555, 'backup_char', 'test')").execute(&pool).await?;
+        let archive = export_guild(5, &[55], &pool).await?.expect("guild is stored");
+        assert_eq!(archive.version, ARCHIVE_VERSION);
+        assert_eq!(archive.users[0].characters[0].df_id, 555);
+        // the archive must survive being written to and read from a file
+        let archive: GuildArchive = serde_json::from_slice(&serde_json::to_vec(&archive)?)?;
+        query!("delete from df_characters where discord_id = 55").execute(&pool).await?;
+        query!("delete from users where discord_id = 55").execute(&pool).await?;
+        let summary = restore_guild(archive, 6, "restore_test", &pool).await?;
+        assert_eq!(summary.characters, 1);
+        assert_eq!(summary.requirements, roles.requirements().len());
+        let restored = get_collection(6, ROLES_LIST, &pool).await?;
+        assert_eq!(restored.requirements().len(), roles.requirements().len());
+        let settings = query!("select announcement_channel_id from guild_settings where guild_id = 6")
+            .fetch_one(&pool)
+            .await?;
+        // channels don't carry over to another guild
+        assert!(settings.announcement_channel_id.is_none());
+        query!("delete from df_characters where discord_id = 55").execute(&pool).await?;
+        query!("delete from users where discord_id = 55").execute(&pool).await?;
+        query!("delete from guild_settings where guild_id = 5 or guild_id = 6").execute(&pool).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        bulk_import::{import_report, parse_import, register_rows, ImportFormat, ImportStatus},
+        db::establish_connection,
+        manage_users::insert_registration,
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    use std::collections::HashMap;
+    #[test]
+    fn parse_import_test() -> Result<()> {
+        let csv = "discord_id,df_id\n92,921\n\n92,abc\n93,921\n\"93\", 922\n";
+        let (rows, invalid) = parse_import(csv, ImportFormat::Csv)?;
+        assert_eq!(
+            rows.iter().map(|r| (r.line, r.df_id)).collect::<Vec<_>>(),
+            vec![(2, 921), (6, 922)]
+        );
+        // the header is skipped, the bad id and the duplicate are not
+        assert_eq!(
+            invalid.iter().map(|r| r.line).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        let json = r#"[{"discord_id": "92", "df_id": 921}, {"discord_id": 93}]"#;
+        let (rows, invalid) = parse_import(json, ImportFormat::Json)?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(invalid[0].line, 2);
+        assert!(parse_import("", ImportFormat::Csv).is_err());
+        Ok(())
+    }
+    #[tokio::test]
+    async fn db_register_rows_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        insert_registration(&pool, 93, "owner", 923, "taken", "test").await?;
+        let (rows, _) = parse_import("92,921\n92,922\n92,923\n94,924", ImportFormat::Csv)?;
+        let discord_names = HashMap::from([(92, "import_user".to_owned())]);
+        let character_names = HashMap::from([(921, Some("imported".to_owned())), (922, None)]);
+        let results = register_rows(&rows, &discord_names, &character_names, "test", &pool).await?;
+        let statuses: Vec<ImportStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ImportStatus::Registered,
+                ImportStatus::NotFound,
+                ImportStatus::AlreadyRegistered,
+                ImportStatus::Invalid
+            ]
+        );
+        let report = import_report(&results);
+        assert!(report
+            .lines()
+            .nth(1)
+            .unwrap()
+            .ends_with("Registered,\"imported\""));
+        query!("delete from df_characters where discord_id = 92 or discord_id = 93")
+            .execute(&pool)
+            .await?;
+        query!("delete from users where discord_id = 92 or discord_id = 93")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
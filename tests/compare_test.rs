@@ -0,0 +1,79 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        parsing::{DFCharacterData, FileFetcher, ParsingCategory},
+        sheets::{compare_items, compare_sheet, compare_stats, StatValue},
+    };
+    use color_eyre::Result;
+    async fn load(path: &str) -> Result<DFCharacterData> {
+        FileFetcher::new(path)
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()
+    }
+    #[tokio::test]
+    async fn compare_items_test() -> Result<()> {
+        let chars = vec![
+            load("htmls/ruleofnine.html").await?,
+            load("htmls/mof.html").await?,
+            load("htmls/3ach.html").await?,
+        ];
+        let comparison = compare_items(&chars);
+        assert_eq!(comparison.names(), vec!["Ruleofnine", "Mof", "3ach"]);
+        assert!(!comparison.shared.is_empty());
+        for name in &comparison.shared {
+            assert_eq!(comparison.matrix[name].owners(), 3);
+        }
+        for (i, char) in comparison.characters.iter().enumerate() {
+            assert!(!char.unique.is_empty());
+            for name in &char.unique {
+                let row = &comparison.matrix[name];
+                assert_eq!(row.owners(), 1);
+                assert!(row.amounts[i] > 0);
+            }
+            for name in &char.missing {
+                let row = &comparison.matrix[name];
+                assert_eq!(row.owners(), 2);
+                assert_eq!(row.amounts[i], 0);
+            }
+        }
+        // with two characters one's unique items are the other's missing items
+        let pair = compare_items(&chars[..2]);
+        assert_eq!(pair.characters[0].unique, pair.characters[1].missing);
+        assert_eq!(pair.characters[1].unique, pair.characters[0].missing);
+        let sheet = compare_sheet(&chars)?;
+        assert!(sheet.buf.starts_with(b"PK"));
+        Ok(())
+    }
+    #[tokio::test]
+    async fn compare_stats_test() -> Result<()> {
+        let chars = vec![
+            load("htmls/ruleofnine.html").await?,
+            load("htmls/mof.html").await?,
+            load("htmls/just_name.html").await?,
+        ];
+        let comparison = compare_stats(&chars);
+        let stat = |label: &str| comparison.stats.iter().find(|s| s.label == label).unwrap();
+        assert_eq!(stat("Level").winners, vec![0, 1]);
+        assert_eq!(stat("DMK").winners, vec![0, 1]);
+        assert_eq!(stat("DMK").values[2], StatValue::Missing);
+        assert_eq!(
+            stat("Dragon").values[1],
+            StatValue::Text("Runescape (Ice)".to_string())
+        );
+        let snowfight = comparison
+            .wars
+            .iter()
+            .find(|w| w.label == "The Grand Snowfight")
+            .unwrap();
+        assert_eq!(snowfight.values[1], StatValue::Number(10001));
+        assert_eq!(snowfight.winners, vec![1]);
+        // ties have no winner
+        let level = &compare_stats(&chars[..2]).stats[0];
+        assert!(level.winners.is_empty());
+        Ok(())
+    }
+}
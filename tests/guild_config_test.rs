@@ -0,0 +1,50 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        db::establish_connection,
+        guild_config::{get_guild_config, save_guild_config, GuildConfig, GuildConfigCache},
+        lookup_df::LookupCategory,
+        requirements::EvaluationMode,
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    #[test]
+    fn config_validation_test() {
+        let mut config = GuildConfig::new(3);
+        assert!(config.set_prefix(Some("toolong".to_string())).is_err());
+        assert!(config.set_prefix(Some("a b".to_string())).is_err());
+        assert!(config.set_prefix(Some("?".to_string())).is_ok());
+        assert!(config.set_timezone(Some("Not/AZone")).is_err());
+        assert!(config.set_timezone(Some("America/New_York")).is_ok());
+        assert_eq!(config.timezone(), Some(chrono_tz::America::New_York));
+        assert_eq!(config.default_lookup(), LookupCategory::CharacterPage);
+        assert_eq!(config.evaluation_mode(), EvaluationMode::Highest);
+    }
+    #[tokio::test]
+    async fn db_guild_config_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        let cache = GuildConfigCache::default();
+        assert!(get_guild_config(3, &pool).await?.prefix().is_none());
+        let mut config = GuildConfig::new(3);
+        config.set_prefix(Some("?".to_string())).unwrap();
+        config.set_default_lookup(Some(LookupCategory::Roles));
+        config.set_evaluation_mode(Some(EvaluationMode::All));
+        config.set_ephemeral(true);
+        save_guild_config(&config, "config_test", &pool).await?;
+        let cached = cache.get(3, &pool).await?;
+        assert_eq!(cached.prefix().as_deref(), Some("?"));
+        assert_eq!(cached.default_lookup(), LookupCategory::Roles);
+        assert_eq!(cached.evaluation_mode(), EvaluationMode::All);
+        assert!(cached.ephemeral());
+        config.set_prefix(None).unwrap();
+        save_guild_config(&config, "config_test", &pool).await?;
+        assert_eq!(cache.get(3, &pool).await?.prefix().as_deref(), Some("?"));
+        cache.invalidate(3);
+        assert!(cache.get(3, &pool).await?.prefix().is_none());
+        query!("delete from guild_settings where guild_id = 3").execute(&pool).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        db::{establish_connection, query_with_id},
+        manage_users::{
+            get_user_characters, insert_registration, reorder_character, search_characters,
+            selected_user, set_main_character, transfer_character_to, CharacterChoice,
+        },
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use poise::serenity_prelude::CommandDataOption;
+    use sqlx::query;
+    #[tokio::test]
+    async fn db_main_character_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        for (df_id, name) in [(901, "first"), (902, "second"), (903, "third")] {
+            insert_registration(&pool, 90, "main_user", df_id, name, "test").await?;
+        }
+        // the earliest registration is the default until a main is picked
+        assert_eq!(query_with_id(&pool, 90).await?, Some(901));
+        assert!(set_main_character(90, 903, &pool).await?);
+        assert!(set_main_character(90, 902, &pool).await?);
+        assert!(!set_main_character(91, 902, &pool).await?);
+        assert_eq!(query_with_id(&pool, 90).await?, Some(902));
+        assert!(reorder_character(90, 903, 1, &pool).await?);
+        let order: Vec<i32> = get_user_characters(90, &pool)
+            .await?
+            .iter()
+            .map(|c| *c.df_id())
+            .collect();
+        // the main stays on top whatever its position
+        assert_eq!(order, vec![902, 903, 901]);
+        assert!(get_user_characters(90, &pool).await?[0].embed_line().starts_with('⭐'));
+        // positions count alts only, so 2 is the last alt and not the main's slot
+        assert!(reorder_character(90, 901, 2, &pool).await?);
+        assert!(reorder_character(90, 902, 3, &pool).await?);
+        let order: Vec<i32> = get_user_characters(90, &pool)
+            .await?
+            .iter()
+            .map(|c| *c.df_id())
+            .collect();
+        assert_eq!(order, vec![902, 903, 901]);
+        query!("delete from df_characters where discord_id = 90").execute(&pool).await?;
+        query!("delete from users where discord_id = 90").execute(&pool).await?;
+        Ok(())
+    }
+    #[tokio::test]
+    async fn db_search_characters_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        for (df_id, name) in [(951, "Dragonlord"), (952, "Drakel"), (953, "Doomknight")] {
+            insert_registration(&pool, 95, "search_user", df_id, name, "test").await?;
+        }
+        let names = |chars: Vec<CharacterChoice>| -> Vec<String> {
+            chars.iter().map(|c| c.character_name().clone()).collect()
+        };
+        let found = names(search_characters(Some(95), "dragonlrd", &pool).await?);
+        assert_eq!(found.first().map(String::as_str), Some("Dragonlord"));
+        // wildcards are matched literally
+        assert!(search_characters(Some(95), "%", &pool).await?.is_empty());
+        let found = search_characters(Some(95), "952", &pool).await?;
+        assert_eq!(*found[0].df_id(), 952);
+        // without input the newest registration comes first
+        assert_eq!(
+            names(search_characters(Some(95), "", &pool).await?)[0],
+            "Doomknight"
+        );
+        assert!(search_characters(None, "", &pool).await?.len() <= 25);
+        query!("delete from df_characters where discord_id = 95")
+            .execute(&pool)
+            .await?;
+        query!("delete from users where discord_id = 95")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+    #[test]
+    fn selected_user_test() -> Result<()> {
+        let options: Vec<CommandDataOption> = serde_json::from_str(
+            r#"[{"name": "user", "type": 6, "value": "123"}, {"name": "character", "type": 4, "value": "9", "focused": true}]"#,
+        )?;
+        assert_eq!(selected_user(&options), Some(123));
+        assert_eq!(selected_user(&options[1..]), None);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn db_transfer_character_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        insert_registration(&pool, 97, "from_user", 971, "moving", "test").await?;
+        set_main_character(97, 971, &pool).await?;
+        assert_eq!(
+            transfer_character_to(&pool, 971, 98, "to_user", "admin", None).await?,
+            Some(97)
+        );
+        assert_eq!(query_with_id(&pool, 97).await?, None);
+        assert_eq!(query_with_id(&pool, 98).await?, Some(971));
+        // the new owner picks their own main
+        assert!(!*get_user_characters(98, &pool).await?[0].is_main());
+        assert_eq!(
+            transfer_character_to(&pool, 979, 98, "to_user", "admin", None).await?,
+            None
+        );
+        let transfers =
+            query!("select count(*) as \"count!\" from character_transfers where df_id = 971")
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(transfers.count, 1);
+        query!("delete from character_transfers where df_id = 971")
+            .execute(&pool)
+            .await?;
+        query!("delete from df_characters where discord_id = 97 or discord_id = 98")
+            .execute(&pool)
+            .await?;
+        query!("delete from users where discord_id = 97 or discord_id = 98")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        db::establish_connection,
+        manage_users::insert_registration,
+        name_refresh::{apply_refresh, log_descriptions, skip_refresh, stale_characters, NameChange},
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    #[tokio::test]
+    async fn db_name_refresh_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        insert_registration(&pool, 96, "refresh_user", 961, "old_name", "test").await?;
+        assert!(stale_characters(&pool, i64::MAX)
+            .await?
+            .iter()
+            .any(|c| c.df_id == 961));
+        let renamed = apply_refresh(&pool, 961, Some("new_name"))
+            .await?
+            .expect("name changed");
+        assert_eq!(
+            renamed.change,
+            NameChange::Renamed {
+                old_name: "old_name".to_owned(),
+                new_name: "new_name".to_owned()
+            }
+        );
+        // checked characters aren't stale until the next week
+        assert!(!stale_characters(&pool, i64::MAX)
+            .await?
+            .iter()
+            .any(|c| c.df_id == 961));
+        assert!(apply_refresh(&pool, 961, Some("new_name")).await?.is_none());
+        let missing = apply_refresh(&pool, 961, None)
+            .await?
+            .expect("flagged not found");
+        assert!(matches!(missing.change, NameChange::NotFound { .. }));
+        // only reported the first time it goes missing
+        assert!(apply_refresh(&pool, 961, None).await?.is_none());
+        let found = apply_refresh(&pool, 961, Some("new_name"))
+            .await?
+            .expect("found again");
+        assert!(matches!(found.change, NameChange::Found { .. }));
+        // a page that failed to parse goes to the back of the queue without a change
+        insert_registration(&pool, 96, "refresh_user", 962, "unparsed", "test").await?;
+        skip_refresh(&pool, 962).await?;
+        assert!(!stale_characters(&pool, i64::MAX)
+            .await?
+            .iter()
+            .any(|c| c.df_id == 962));
+        query!("delete from df_characters where discord_id = 96")
+            .execute(&pool)
+            .await?;
+        query!("delete from users where discord_id = 96")
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+    #[test]
+    fn log_descriptions_test() {
+        assert!(log_descriptions(&[]).is_empty());
+        let short = vec!["a".repeat(10); 3];
+        assert_eq!(log_descriptions(&short), vec![format!("{}\n", short.join("\n"))]);
+        let long = vec!["é".repeat(2500), "b".repeat(3000), "c".repeat(10)];
+        let descriptions = log_descriptions(&long);
+        // an oversized first line is cut instead of leaving an empty description
+        assert_eq!(descriptions.len(), 2);
+        assert!(descriptions.iter().all(|d| !d.is_empty() && d.len() <= 4001));
+        assert!(descriptions[1].ends_with("c\n"));
+    }
+}
@@ -0,0 +1,33 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::onboarding::{
+        parse_choice, parse_id, permissions_summary, selected_id, setup_summary,
+    };
+    use poise::serenity_prelude::Permissions;
+    #[test]
+    fn wizard_choice_test() {
+        let wizard_id = "onboarding42";
+        assert_eq!(parse_choice(wizard_id, "onboarding42start", &[]), "start");
+        let choice = parse_choice(wizard_id, "onboarding42channel", &["1234".to_string()]);
+        assert_eq!(choice, "channel:1234");
+        assert_eq!(selected_id(&choice, "channel"), Some(1234));
+        assert_eq!(selected_id(&choice, "role"), None);
+        assert_eq!(selected_id("skip", "channel"), None);
+        assert_eq!(parse_id("<#5678>"), Some(5678));
+        assert_eq!(parse_id("<@&91>"), Some(91));
+        assert_eq!(parse_id("general"), None);
+    }
+    #[test]
+    fn wizard_summary_test() {
+        let permissions = permissions_summary(Some(Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES));
+        assert!(permissions.contains("✅ Send Messages"));
+        assert!(permissions.contains("❌ Embed Links"));
+        assert!(permissions_summary(None).contains("No announcement channel"));
+        let summary = setup_summary(Some(1), None, Some("subscribed to **Roles**"), &permissions);
+        assert!(summary.contains("**Announcement Channel:** <#1>"));
+        assert!(summary.contains("**Ping Role:** *not set*"));
+        assert!(summary.contains("**Requirements:** subscribed to **Roles**"));
+        assert!(summary.contains(&permissions));
+    }
+}
@@ -7,7 +7,8 @@ mod tests {
     #[test]
     fn paginate_test() -> Result<()> {
         let reqs = get_requirements_file("InnList.json")?;
-        let pages = get_requirement_pages(reqs,None);
+        let (pages, categories) = get_requirement_pages(&reqs,None);
+        assert_eq!(categories.len(), 1);
         pages.iter().for_each(|page|assert!(page.len()<4096));
         Ok(())
     }
@@ -0,0 +1,46 @@
+extern crate achivit_rs;
+#[cfg(test)]
+mod tests {
+    use achivit_rs::{
+        db::establish_connection,
+        manage_users::insert_registration,
+        registration::{delete_request, insert_request, review_request, review_request_in, RegistrationStatus},
+    };
+    use color_eyre::Result;
+    use dotenv::dotenv;
+    use sqlx::query;
+    #[tokio::test]
+    async fn db_registration_request_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        query!("insert into guild_settings (guild_name,guild_id) VALUES ('registration_test',8)").execute(&pool).await?;
+        let request = insert_request(8, 88, "request_user", 888, "request_char", &pool)
+            .await?
+            .expect("first request is queued");
+        // only one pending request per character
+        assert!(insert_request(8, 89, "other_user", 888, "request_char", &pool).await?.is_none());
+        // a review whose transaction is rolled back leaves the request pending
+        let mut transaction = pool.begin().await?;
+        assert!(review_request_in(&mut transaction, *request.request_id(), RegistrationStatus::Denied, "admin")
+            .await?
+            .is_some());
+        transaction.rollback().await?;
+        let approved = review_request(*request.request_id(), RegistrationStatus::Approved, "admin", &pool)
+            .await?
+            .expect("request was pending");
+        assert_eq!(approved.status(), "Approved");
+        assert!(review_request(*request.request_id(), RegistrationStatus::Denied, "admin", &pool).await?.is_none());
+        // a deleted request frees the character for a new one
+        let retry = insert_request(8, 89, "other_user", 888, "request_char", &pool)
+            .await?
+            .expect("the reviewed request no longer blocks");
+        delete_request(*retry.request_id(), &pool).await?;
+        assert!(insert_request(8, 89, "other_user", 888, "request_char", &pool).await?.is_some());
+        assert!(insert_registration(&pool, 88, "request_user", 888, "request_char", "admin").await?);
+        assert!(!insert_registration(&pool, 89, "other_user", 888, "request_char", "admin").await?);
+        query!("delete from df_characters where discord_id = 88 or discord_id = 89").execute(&pool).await?;
+        query!("delete from users where discord_id = 88 or discord_id = 89").execute(&pool).await?;
+        query!("delete from guild_settings where guild_id = 8").execute(&pool).await?;
+        Ok(())
+    }
+}
@@ -3,13 +3,21 @@ extern crate achivit_rs;
 mod tests {
     use achivit_rs::{
         db::establish_connection,
+        leaderboard::{rank_characters, rank_members},
         parsing::{FileFetcher, ParsingCategory},
-        requirements::{check_requirements, get_requirements_file},
-        guild_settings::insert_requirements,
+        requirement_lists::{upsert_list, INN_COLLECTION},
+        requirements::{
+            check_requirements, check_shared_requirements, evaluate_member_requirements,
+            evaluate_requirements, get_requirements_bytes, EvaluationMode,
+            excluded_requirements, get_requirements_file, ladder_progress,
+            requirement_holders, RequirementCache, RequirementList,
+        },
+        guild_settings::{insert_collection, insert_requirements, insert_shared_list},
     };
     use color_eyre::Result;
     use dotenv::dotenv;
     use sqlx::query;
+    use std::collections::HashMap;
     #[test]
     fn inn_loads() -> Result<()> {
         get_requirements_file("InnList.json")?;
@@ -19,9 +27,12 @@ mod tests {
     async fn db_ascends_test()->Result<()>{
         dotenv().ok();
         let pool = establish_connection().await?;
+        let cache = RequirementCache::default();
         let reqs = get_requirements_file("ascendancies.json")?;
         query!("insert into guild_settings (guild_name,guild_id) VALUES ('ascend_test',0)").execute(&pool).await?;
-        insert_requirements(0, &pool, &reqs).await?;
+        insert_requirements(0, &pool, &cache, &reqs).await?;
+        let inn_list = get_requirements_file("InnList.json")?;
+        insert_collection(0, INN_COLLECTION, &pool, &cache, &inn_list).await?;
         let ruleofnine = FileFetcher::new("htmls/ruleofnine.html")
             .category(ParsingCategory::Items)
             .fetch_data()
@@ -40,9 +51,9 @@ mod tests {
             .await?
             .to_lookupstate()?
             .extract_character_data()?;
-        let ruleofnine_ascends = check_requirements(&ruleofnine, 0, &pool).await?;
-        let just_name_ascends = check_requirements(&just_name, 0, &pool).await?;
-        let ach_roles_ascends = check_requirements(&ach, 0, &pool).await?;
+        let ruleofnine_ascends = check_requirements(&ruleofnine, 0, &pool, &cache).await?;
+        let just_name_ascends = check_requirements(&just_name, 0, &pool, &cache).await?;
+        let ach_roles_ascends = check_requirements(&ach, 0, &pool, &cache).await?;
         query!("delete from guild_settings where guild_id = 0").execute(&pool).await?;
         assert_eq!(ach_roles_ascends.requirements().len(),5);
         assert_eq!(ruleofnine_ascends.requirements().len(),11);
@@ -54,34 +65,251 @@ mod tests {
     async fn db_roles_test() -> Result<()> {
         dotenv().ok();
         let pool = establish_connection().await?;
+        let cache = RequirementCache::default();
         let reqs = get_requirements_file("roles.json")?;
         query!("insert into guild_settings (guild_name,guild_id) VALUES ('role_test',1)").execute(&pool).await?;
-        insert_requirements(1, &pool, &reqs).await?;
+        insert_requirements(1, &pool, &cache, &reqs).await?;
         let ruleofnine = FileFetcher::new("htmls/ruleofnine.html")
             .category(ParsingCategory::Items)
             .fetch_data()
             .await?
             .to_lookupstate()?
             .extract_character_data()?;
-        let ruleofnine_roles = check_requirements(&ruleofnine, 1, &pool).await?;
+        let ruleofnine_roles = check_requirements(&ruleofnine, 1, &pool, &cache).await?;
         let just_name = FileFetcher::new("htmls/just_name.html")
             .category(ParsingCategory::Items)
             .fetch_data()
             .await?
             .to_lookupstate()?
             .extract_character_data()?;
-        let just_name_roles = check_requirements(&just_name, 1, &pool).await?;
+        let just_name_roles = check_requirements(&just_name, 1, &pool, &cache).await?;
         let ach = FileFetcher::new("htmls/3ach.html")
             .category(ParsingCategory::Items)
             .fetch_data()
             .await?
             .to_lookupstate()?
             .extract_character_data()?;
-        let ach_roles = check_requirements(&ach, 1, &pool).await?;
+        let ach_roles = check_requirements(&ach, 1, &pool, &cache).await?;
         query!("delete from guild_settings where guild_id = 1").execute(&pool).await?;
         assert_eq!(0, just_name_roles.requirements().len());
         assert_eq!(14, ruleofnine_roles.requirements().len());
         assert_eq!(17, ach_roles.requirements().len());
         Ok(())
     }
+    #[tokio::test]
+    async fn db_shared_list_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        let cache = RequirementCache::default();
+        let reqs = get_requirements_file("roles.json")?;
+        query!("insert into guild_settings (guild_name,guild_id) VALUES ('shared_test',2)").execute(&pool).await?;
+        insert_shared_list("shared_test", None, Some("test list"), &pool, &cache, &reqs).await?;
+        let ruleofnine = FileFetcher::new("htmls/ruleofnine.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        let unsubscribed_roles = check_requirements(&ruleofnine, 2, &pool, &cache).await?;
+        query!("insert into list_subscriptions (guild_id,list_id) select 2, list_id from requirement_lists where guild_id is null and name = 'shared_test'").execute(&pool).await?;
+        let cached_roles = check_requirements(&ruleofnine, 2, &pool, &cache).await?;
+        cache.invalidate_guild(2).await;
+        let subscribed_roles = check_requirements(&ruleofnine, 2, &pool, &cache).await?;
+        let shared_roles = check_shared_requirements(&ruleofnine, "shared_test", &pool, &cache).await?;
+        query!("delete from guild_settings where guild_id = 2").execute(&pool).await?;
+        query!("delete from requirement_lists where guild_id is null and name = 'shared_test'").execute(&pool).await?;
+        assert_eq!(0, unsubscribed_roles.requirements().len());
+        assert_eq!(0, cached_roles.requirements().len());
+        assert_eq!(14, subscribed_roles.requirements().len());
+        assert_eq!(14, shared_roles.requirements().len());
+        Ok(())
+    }
+    #[tokio::test]
+    async fn list_name_scope_test() -> Result<()> {
+        dotenv().ok();
+        let pool = establish_connection().await?;
+        query!("insert into guild_settings (guild_name,guild_id) VALUES ('scope_test',10)").execute(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let shared_id = upsert_list(&mut conn, None, "scope_test", None, None).await?;
+        let guild_id = upsert_list(&mut conn, Some(10), "scope_test", None, None).await?;
+        let shared_again = upsert_list(&mut conn, None, "scope_test", None, Some("updated")).await?;
+        query!("delete from guild_settings where guild_id = 10").execute(&pool).await?;
+        query!("delete from requirement_lists where name = 'scope_test'").execute(&pool).await?;
+        assert_ne!(shared_id, guild_id);
+        assert_eq!(shared_id, shared_again);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn role_holders_test() -> Result<()> {
+        let roles = get_requirements_file("roles.json")?;
+        let mut members = vec![];
+        for file in ["htmls/ruleofnine.html", "htmls/just_name.html", "htmls/3ach.html"] {
+            let char = FileFetcher::new(file)
+                .category(ParsingCategory::Items)
+                .fetch_data()
+                .await?
+                .to_lookupstate()?
+                .extract_character_data()?;
+            members.push(vec![char]);
+        }
+        let ach_roles = evaluate_requirements(&roles, &members[2][0], None);
+        let holders = requirement_holders(&roles, &members, None);
+        assert_eq!(holders.len(), roles.requirements().len());
+        assert!(holders.iter().all(|(_, count)| *count <= 2));
+        for role in ach_roles.requirements() {
+            assert!(holders.iter().any(|(r, count)| r.name() == role.name() && *count > 0));
+        }
+        Ok(())
+    }
+    #[tokio::test]
+    async fn seasonal_requirements_test() -> Result<()> {
+        let roles = get_requirements_file("roles.json")?;
+        let ach = FileFetcher::new("htmls/3ach.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        let earned = evaluate_requirements(&roles, &ach, None);
+        let role = earned.requirements()[0].name().clone();
+        let with_window = |window: serde_json::Value| -> Result<RequirementList> {
+            let mut json = serde_json::to_value(&roles)?;
+            for req in json.as_array_mut().unwrap() {
+                if req["name"] == role.as_str() {
+                    req.as_object_mut().unwrap().extend(window.as_object().unwrap().clone());
+                }
+            }
+            Ok(serde_json::from_value(json)?)
+        };
+        let has_role = |list: &RequirementList| {
+            evaluate_requirements(list, &ach, None)
+                .requirements()
+                .iter()
+                .any(|r| r.name() == &role)
+        };
+        let last_played = ach.last_played;
+        let open = with_window(serde_json::json!({"start": "2000-01-01", "end": "2999-01-01"}))?;
+        let expired = with_window(serde_json::json!({"end": "2000-01-01"}))?;
+        let upcoming = with_window(serde_json::json!({"start": "2999-01-01"}))?;
+        let inactive = with_window(serde_json::json!({
+            "start": last_played.succ_opt().unwrap(),
+            "require_active": true
+        }))?;
+        let active = with_window(serde_json::json!({"start": last_played, "require_active": true}))?;
+        assert!(has_role(&open));
+        assert!(!has_role(&expired));
+        assert!(!has_role(&upcoming));
+        assert!(!has_role(&inactive));
+        assert!(has_role(&active));
+        Ok(())
+    }
+    #[tokio::test]
+    async fn leaderboard_test() -> Result<()> {
+        let roles = get_requirements_file("roles.json")?;
+        let mut members = HashMap::new();
+        for (discord_id, file) in [(1, "htmls/ruleofnine.html"), (2, "htmls/just_name.html"), (2, "htmls/3ach.html")] {
+            let char = FileFetcher::new(file)
+                .category(ParsingCategory::Items)
+                .fetch_data()
+                .await?
+                .to_lookupstate()?
+                .extract_character_data()?;
+            members.entry(discord_id).or_insert_with(Vec::new).push(char);
+        }
+        let ranked = rank_characters(&roles, &members, None);
+        let scores: Vec<i32> = ranked.iter().map(|e| *e.score()).collect();
+        assert_eq!(scores, vec![17, 14, 0]);
+        let ranked_members = rank_members(ranked);
+        assert_eq!(ranked_members.len(), 2);
+        assert_eq!(*ranked_members[0].discord_id(), 2);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn ladder_test() -> Result<()> {
+        let ladders = get_requirements_bytes(
+            br#"[
+                {"name": "Hoarder {}", "description": "Have {} gold", "type": "Gold", "thresholds": [2147483647, 0, 1]},
+                {"name": "Veteran", "description": "Fight in wars", "type": "Wars", "thresholds": [0]}
+            ]"#,
+        )?;
+        assert_eq!(ladders.requirements().len(), 4);
+        let ruleofnine = FileFetcher::new("htmls/ruleofnine.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        let earned = evaluate_requirements(&ladders, &ruleofnine, None);
+        let names: Vec<&String> = earned.requirements().iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["Hoarder 1", "Veteran 1"]);
+        let progress = ladder_progress(&ladders, &ruleofnine, None);
+        let hoarder = progress.iter().find(|p| p.ladder == "Hoarder").unwrap();
+        assert_eq!(hoarder.earned.unwrap().name(), "Hoarder 1");
+        assert_eq!(hoarder.next.unwrap().name(), "Hoarder 2,147,483,647");
+        assert_eq!(hoarder.current, Some(ruleofnine.gold));
+        let veteran = progress.iter().find(|p| p.ladder == "Veteran").unwrap();
+        assert!(veteran.next.is_none());
+        Ok(())
+    }
+    #[tokio::test]
+    async fn exclusion_test() -> Result<()> {
+        let roles = get_requirements_bytes(
+            br#"[
+                {"name": "NDA Only", "type": "Exclusion", "excluded_tags": ["DA"]},
+                {"name": "No Artifacts", "type": "Exclusion", "excluded_tags": ["ARTIFACT"]},
+                {"name": "Nothing Missing", "type": "Exclusion", "excluded": ["Not A Real Item"]}
+            ]"#,
+        )?;
+        let just_name = FileFetcher::new("htmls/just_name.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        let ruleofnine = FileFetcher::new("htmls/ruleofnine.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        assert_eq!(evaluate_requirements(&roles, &just_name, None).requirements().len(), 3);
+        let earned = evaluate_requirements(&roles, &ruleofnine, None);
+        assert!(!earned.requirements().iter().any(|r| r.name() == "NDA Only"));
+        assert!(earned.requirements().iter().any(|r| r.name() == "Nothing Missing"));
+        let excluded = excluded_requirements(&roles, &ruleofnine, None);
+        let (role, violations) = excluded.iter().find(|(r, _)| r.name() == "NDA Only").unwrap();
+        assert_eq!(role.name(), "NDA Only");
+        assert!(violations[0].ends_with("DA items"));
+        Ok(())
+    }
+    #[tokio::test]
+    async fn member_requirements_test() -> Result<()> {
+        let roles = get_requirements_bytes(
+            br#"[
+                {"name": "NDA Only", "type": "Exclusion", "excluded_tags": ["DA"]},
+                {"name": "Hoarder {}", "description": "Have {} gold", "type": "Gold", "thresholds": [1]}
+            ]"#,
+        )?;
+        let just_name = FileFetcher::new("htmls/just_name.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        let ruleofnine = FileFetcher::new("htmls/ruleofnine.html")
+            .category(ParsingCategory::Items)
+            .fetch_data()
+            .await?
+            .to_lookupstate()?
+            .extract_character_data()?;
+        let earned = evaluate_member_requirements(
+            &roles,
+            &[just_name, ruleofnine],
+            None,
+            EvaluationMode::Highest,
+        );
+        let names: Vec<&String> = earned.requirements().iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["Hoarder 1", "NDA Only"]);
+        Ok(())
+    }
 }